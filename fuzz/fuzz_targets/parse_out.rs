@@ -0,0 +1,33 @@
+// Feeds arbitrary bytes to every decoder of the interpreter's output, in
+// both formats; none of them may panic, whatever the output. Run with
+//
+//     cargo fuzz run parse_out
+//
+// from the repository's root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// the game is a binary, so the decoders are built into the target as is,
+// from the game's src directory.
+#[allow(dead_code)]
+#[path = "../../src"]
+mod game {
+    pub mod parse_out;
+}
+
+use game::parse_out::OutputFormat;
+
+fuzz_target!(|data: &[u8]| {
+    let s = String::from_utf8_lossy(data);
+    let _ = OutputFormat::detect(&s);
+    for &format in [OutputFormat::Named, OutputFormat::DeBruijn].iter() {
+        let _ = format.parse_church_bool(&s);
+        let _ = format.clni_to_int(&s);
+        let _ = format.parse_string(&s);
+        let _ = format.parse_int_list(&s);
+        let _ = format.parse_rect_list(&s, 5, 200, 150);
+        let _ = format.parse_rect_list(&s, i32::MAX, i32::MAX, i32::MIN);
+    }
+});
@@ -0,0 +1,567 @@
+// config.rs: the config file, for key bindings, colors and the native
+// backend's parameters.
+//
+// The file is a small subset of TOML: [sections] of key = value lines, where
+// values are integers, booleans, strings or arrays of those, all on one line.
+// Any key can also be set from the command line, which overrides the file.
+// Errors point at the file's line, or the option, and at the offending key.
+//
+// [keys]
+// up = ["Up", "K"]
+// quit = "Escape"
+//
+// [colors]
+// foreground = "#33ff33"
+//
+// [window]
+// width = 1600
+// height = 1200
+//
+// [native]
+// win_score = 5
+//
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs,
+};
+
+use sdl2::keyboard::Keycode;
+
+use crate::{
+    input::{self, KeyBindings},
+    pong::{self, Tunables, WINDOW_WIDTH, WINDOW_HEIGHT},
+};
+
+// the biggest window that can be asked for, in either direction.
+const MAX_WINDOW_SIZE:i64 = 16384;
+
+// where values that weren't set come from, in errors.
+const DEFAULT_ORIGIN:&str = "default config";
+
+/// Colors as RGB.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Colors {
+    pub background: [u8; 3],
+    /// Of everything that's drawn: paddles, ball, scores and text.
+    pub foreground: [u8; 3],
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            background: [0, 0, 0],
+            foreground: [0xff, 0xff, 0xff],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    /// The file and line, or the option, where the error is.
+    pub origin: String,
+    /// The offending key, as section.key, if the error is about one.
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.key {
+            Some(ref key) => write!(f, "{}: {}: {}", self.origin, key, self.message),
+            None => write!(f, "{}: {}", self.origin, self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// For the window, with either backend.
+    pub keys: KeyBindings,
+    /// For the window and saved frames, with either backend.
+    pub colors: Colors,
+    /// The window's initial size.
+    pub window_size: (u32, u32),
+    /// Only for the native backend.
+    pub native: Tunables,
+    // where each key was last set, for the errors found once everything is
+    // read.
+    origins: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            keys: KeyBindings::default(),
+            colors: Colors::default(),
+            window_size: (WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32),
+            native: Tunables::default(),
+            origins: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "an integer",
+            Value::Bool(_) => "a boolean",
+            Value::Str(_) => "a string",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+impl Config {
+    /// The config from the file, if any, with the command line's changes,
+    /// validated.
+    ///
+    pub fn load(filename: Option<&str>, overrides: &[String]) -> Result<Config, Error> {
+        let mut config = match filename {
+            Some(filename) => Config::read(filename)?,
+            None => Config::default(),
+        };
+        for assignment in overrides {
+            config.set(assignment)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read the config file on top of the defaults. The result still has to
+    /// be validated, after any changes from the command line.
+    ///
+    pub fn read(filename: &str) -> Result<Config, Error> {
+        let text = fs::read_to_string(filename).map_err(|e| Error {
+            origin: filename.to_string(),
+            key: None,
+            message: e.to_string(),
+        })?;
+        let mut config = Config::default();
+        config.apply_toml(&text, filename)?;
+        Ok(config)
+    }
+
+    fn apply_toml(&mut self, text: &str, filename: &str) -> Result<(), Error> {
+        let mut section: Option<String> = None;
+        let mut seen = HashSet::new();
+        for (i, line) in text.lines().enumerate() {
+            let origin = format!("{}:{}", filename, i + 1);
+            let error = |key: Option<&str>, message: String| Error {
+                origin: origin.clone(),
+                key: key.map(str::to_string),
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix('[') {
+                let name = match rest.find(']') {
+                    Some(end) if is_comment(&rest[end + 1..]) => rest[..end].trim(),
+                    _ => return Err(error(None, "invalid section header".to_string())),
+                };
+                if !is_section(name) {
+                    return Err(error(None, format!("unknown section '[{}]'", name)));
+                }
+                section = Some(name.to_string());
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(at) => (line[..at].trim(), &line[at + 1..]),
+                None => return Err(error(None, "expected 'key = value'".to_string())),
+            };
+            // as in TOML, keys are relative to the section, so a dotted key
+            // only names a section's key before the first section.
+            let full_key = match section {
+                Some(ref section) => format!("{}.{}", section, key),
+                None if key.contains('.') => key.to_string(),
+                None => return Err(error(Some(key), "key outside of any section".to_string())),
+            };
+            if !seen.insert(full_key.clone()) {
+                return Err(error(Some(&full_key), "set more than once".to_string()));
+            }
+            let value = parse_value(value).map_err(|e| error(Some(&full_key), e))?;
+            self.set_value(&full_key, value).map_err(|e| error(Some(&full_key), e))?;
+            self.origins.insert(full_key, origin);
+        }
+        Ok(())
+    }
+
+    /// Set a single key from the command line, as section.key=value. The
+    /// value is as in the file, except that strings don't need quotes.
+    ///
+    pub fn set(&mut self, assignment: &str) -> Result<(), Error> {
+        let origin = format!("--set '{}'", assignment);
+        let error = |key: Option<&str>, message: String| Error {
+            origin: origin.clone(),
+            key: key.map(str::to_string),
+            message,
+        };
+        let (key, value) = match assignment.find('=') {
+            Some(at) => (assignment[..at].trim(), &assignment[at + 1..]),
+            None => return Err(error(None, "expected 'section.key=value'".to_string())),
+        };
+        let value = parse_value(value)
+            .unwrap_or_else(|_| Value::Str(value.trim().to_string()));
+        self.set_value(key, value).map_err(|e| error(Some(key), e))?;
+        self.origins.insert(key.to_string(), origin);
+        Ok(())
+    }
+
+    fn set_value(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let native = &mut self.native;
+        match key {
+            "keys.up" => self.keys.paddle.up = keycodes(value)?,
+            "keys.down" => self.keys.paddle.down = keycodes(value)?,
+            "keys.second_up" => self.keys.second_paddle.up = keycodes(value)?,
+            "keys.second_down" => self.keys.second_paddle.down = keycodes(value)?,
+            "keys.pause" => self.keys.pause = keycodes(value)?,
+            "keys.serve" => self.keys.serve = keycodes(value)?,
+            "keys.restart" => self.keys.restart = keycodes(value)?,
+            "keys.quit" => self.keys.quit = keycodes(value)?,
+
+            "colors.background" => self.colors.background = color(value)?,
+            "colors.foreground" => self.colors.foreground = color(value)?,
+
+            "window.width" => self.window_size.0 = int_in(value, 1, MAX_WINDOW_SIZE)? as u32,
+            "window.height" => self.window_size.1 = int_in(value, 1, MAX_WINDOW_SIZE)? as u32,
+
+            "native.step_size" => native.step_size = int_in(value, 1, WINDOW_HEIGHT as i64)? as i32,
+            "native.bar_height" => native.bar_height = int_in(value, 1, WINDOW_HEIGHT as i64)? as i32,
+            "native.ball_size" => native.ball_size = int_in(value, 1, WINDOW_WIDTH as i64)? as u32,
+            "native.win_score" => native.win_score = int_in(value, 1, pong::MAX_WIN_SCORE as i64)? as usize,
+
+            _ => match key.split('.').next() {
+                Some(section) if is_section(section) => return Err("unknown key".to_string()),
+                _ => return Err("unknown section".to_string()),
+            },
+        }
+        Ok(())
+    }
+
+    /// Check what can only be checked once every key is set: that no key is
+    /// bound twice, and that the native backend's parameters fit together.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        let error = |key: &str, message: String| Error {
+            origin: self.origins.get(key).map_or(DEFAULT_ORIGIN, String::as_str).to_string(),
+            key: Some(key.to_string()),
+            message,
+        };
+
+        let keys = &self.keys;
+        let bindings = [
+            ("keys.up", &keys.paddle.up),
+            ("keys.down", &keys.paddle.down),
+            ("keys.second_up", &keys.second_paddle.up),
+            ("keys.second_down", &keys.second_paddle.down),
+            ("keys.pause", &keys.pause),
+            ("keys.serve", &keys.serve),
+            ("keys.restart", &keys.restart),
+            ("keys.quit", &keys.quit),
+        ];
+        let mut bound: HashMap<Keycode, &str> = HashMap::new();
+        for (key, keycodes) in bindings.iter() {
+            for keycode in keycodes.iter() {
+                let name = input::keycode_name(*keycode);
+                if input::is_reserved(*keycode) {
+                    return Err(error(key, format!("'{}' is reserved for the window or debug mode", name)));
+                }
+                match bound.get(keycode) {
+                    Some(other) if other != key => {
+                        return Err(error(key, format!("'{}' is already bound to {}", name, other)));
+                    },
+                    _ => bound.insert(*keycode, *key),
+                };
+            }
+        }
+
+        self.native.validate()
+            .map_err(|(key, message)| error(&format!("native.{}", key), message))
+    }
+}
+
+fn is_section(name: &str) -> bool {
+    ["keys", "colors", "window", "native"].contains(&name)
+}
+
+fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+fn keycodes(value: Value) -> Result<Vec<Keycode>, String> {
+    let names = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+    names.into_iter()
+        .map(|name| match name {
+            Value::Str(name) => input::keycode_from_name(&name)
+                .ok_or_else(|| format!("unknown key name '{}'", name)),
+            value => Err(format!("expected key names, not {}", value.kind())),
+        })
+        .collect()
+}
+
+fn color(value: Value) -> Result<[u8; 3], String> {
+    let invalid = || "expected a color as \"#rrggbb\"".to_string();
+    let hex = match value {
+        Value::Str(ref s) => s.strip_prefix('#').ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+fn int_in(value: Value, min: i64, max: i64) -> Result<i64, String> {
+    match value {
+        Value::Int(n) if n >= min && n <= max => Ok(n),
+        Value::Int(n) => Err(format!("{} is out of range; expected {} to {}", n, min, max)),
+        value => Err(format!("expected an integer, not {}", value.kind())),
+    }
+}
+
+// Parse a whole value, which may be followed by a comment.
+fn parse_value(text: &str) -> Result<Value, String> {
+    let mut parser = ValueParser {
+        chars: text.char_indices().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_spaces();
+    match parser.chars.peek() {
+        None | Some((_, '#')) => Ok(value),
+        Some((at, c)) => Err(format!("unexpected '{}' at column {}", c, at + 1)),
+    }
+}
+
+struct ValueParser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> ValueParser<'a> {
+    fn skip_spaces(&mut self) {
+        while let Some((_, ' ')) | Some((_, '\t')) = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        match self.chars.peek().copied() {
+            None => Err("missing value".to_string()),
+            Some((_, '"')) | Some((_, '\'')) => self.string(),
+            Some((_, '[')) => self.array(),
+            Some((_, c)) if c.is_ascii_digit() || c == '-' || c == '+' => self.int(),
+            Some((_, c)) if c.is_ascii_alphabetic() => {
+                match self.word().as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    word => Err(format!("invalid value '{}'; strings need quotes", word)),
+                }
+            },
+            Some((at, c)) => Err(format!("unexpected '{}' at column {}", c, at + 1)),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-') {
+                break;
+            }
+            word.push(c);
+            self.chars.next();
+        }
+        word
+    }
+
+    fn int(&mut self) -> Result<Value, String> {
+        let word = self.word();
+        word.replace('_', "")
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| format!("invalid integer '{}'", word))
+    }
+
+    // Basic strings, in double quotes, can have escapes; literal strings, in
+    // single quotes, can't.
+    fn string(&mut self) -> Result<Value, String> {
+        let (_, quote) = self.chars.next().unwrap();
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err("unterminated string".to_string()),
+                Some((_, c)) if c == quote => return Ok(Value::Str(s)),
+                Some((_, '\\')) if quote == '"' => match self.chars.next() {
+                    Some((_, '"')) => s.push('"'),
+                    Some((_, '\\')) => s.push('\\'),
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 't')) => s.push('\t'),
+                    _ => return Err("invalid escape in string".to_string()),
+                },
+                Some((_, c)) => s.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.chars.next();
+        let mut values = Vec::new();
+        loop {
+            self.skip_spaces();
+            if let Some((_, ']')) = self.chars.peek() {
+                self.chars.next();
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_spaces();
+            match self.chars.next() {
+                Some((_, ',')) => {},
+                Some((_, ']')) => return Ok(Value::Array(values)),
+                None => return Err("unterminated array; arrays must be on one line".to_string()),
+                Some((at, c)) => return Err(format!("unexpected '{}' at column {}", c, at + 1)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, Error> {
+        let mut config = Config::default();
+        config.apply_toml(text, "pong.toml")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn error(origin: &str, key: &str, message: &str) -> Error {
+        Error {
+            origin: origin.to_string(),
+            key: Some(key.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse_value(" 1_000 # comment"), Ok(Value::Int(1000)));
+        assert_eq!(parse_value("-3"), Ok(Value::Int(-3)));
+        assert_eq!(parse_value("true"), Ok(Value::Bool(true)));
+        assert_eq!(parse_value(r#""a \"b\"""#), Ok(Value::Str("a \"b\"".to_string())));
+        assert_eq!(parse_value(r"'C:\dir'"), Ok(Value::Str(r"C:\dir".to_string())));
+        assert_eq!(parse_value(r#"["Up", 'K',]"#),
+                   Ok(Value::Array(vec![Value::Str("Up".to_string()), Value::Str("K".to_string())])));
+        assert_eq!(parse_value("[]"), Ok(Value::Array(vec![])));
+        assert!(parse_value("Up").is_err());
+        assert!(parse_value("\"open").is_err());
+        assert!(parse_value("[1, 2").is_err());
+        assert!(parse_value("1 2").is_err());
+        assert!(parse_value("").is_err());
+    }
+
+    #[test]
+    fn full_config() {
+        let config = parse("\
+            # comments and blank lines are skipped\n\
+            \n\
+            native.win_score = 5\n\
+            [keys]\n\
+            up = [\"I\", \"Up\"]\n\
+            down = \",\"\n\
+            quit = [\"Escape\"]\n\
+            [colors]  # trailing comment\n\
+            foreground = \"#33FF00\"\n\
+            [window]\n\
+            width = 1600\n\
+            [native]\n\
+            bar_height = 80\n").unwrap();
+        assert_eq!(config.keys.paddle.up, vec![Keycode::I, Keycode::Up]);
+        assert_eq!(config.keys.paddle.down, vec![Keycode::Comma]);
+        assert_eq!(config.keys.quit, vec![Keycode::Escape]);
+        assert_eq!(config.keys.pause, KeyBindings::default().pause);
+        assert_eq!(config.colors.foreground, [0x33, 0xff, 0x00]);
+        assert_eq!(config.colors.background, [0, 0, 0]);
+        assert_eq!(config.window_size, (1600, WINDOW_HEIGHT as u32));
+        assert_eq!(config.native.win_score, 5);
+        assert_eq!(config.native.bar_height, 80);
+        assert_eq!(config.native.step_size, Tunables::default().step_size);
+    }
+
+    #[test]
+    fn errors_point_at_the_key() {
+        assert_eq!(parse("[native]\n\nstep_size = 0\n").unwrap_err(),
+                   error("pong.toml:3", "native.step_size", "0 is out of range; expected 1 to 600"));
+        // "comma" isn't a key name, but "," is.
+        assert_eq!(parse("[keys]\ndown = [\"Down\", \"comma\"]\n").unwrap_err(),
+                   error("pong.toml:2", "keys.down", "unknown key name 'comma'"));
+        assert_eq!(parse("[native]\nwin_score = \"ten\"\n").unwrap_err(),
+                   error("pong.toml:2", "native.win_score", "expected an integer, not a string"));
+        assert_eq!(parse("[colors]\nbackground = \"red\"\n").unwrap_err(),
+                   error("pong.toml:2", "colors.background", "expected a color as \"#rrggbb\""));
+        assert_eq!(parse("[window]\ncolour = 1\n").unwrap_err(),
+                   error("pong.toml:2", "window.colour", "unknown key"));
+        assert_eq!(parse("[window]\nnative.win_score = 5\n").unwrap_err(),
+                   error("pong.toml:2", "window.native.win_score", "unknown key"));
+        assert_eq!(parse("[keys]\nup = \"I\"\nup = \"K\"\n").unwrap_err(),
+                   error("pong.toml:3", "keys.up", "set more than once"));
+        assert_eq!(parse("up = \"I\"\n").unwrap_err(),
+                   error("pong.toml:1", "up", "key outside of any section"));
+
+        let unknown_section = parse("[sound]\n").unwrap_err();
+        assert_eq!(unknown_section.to_string(), "pong.toml:1: unknown section '[sound]'");
+        assert_eq!(parse("[keys\n").unwrap_err().to_string(), "pong.toml:1: invalid section header");
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(parse("[keys]\npause = \"K\"\n").unwrap_err(),
+                   error("pong.toml:2", "keys.pause", "'K' is already bound to keys.up"));
+        assert_eq!(parse("[keys]\nserve = \"F11\"\n").unwrap_err(),
+                   error("pong.toml:2", "keys.serve",
+                         "'F11' is reserved for the window or debug mode"));
+        // freeing a key for another action works in any order.
+        assert!(parse("[keys]\npause = \"K\"\nup = \"Up\"\n").is_ok());
+
+        let error = parse("[native]\nbar_height = 595\n").unwrap_err();
+        assert_eq!(error.origin, "pong.toml:2");
+        assert_eq!(error.key, Some("native.bar_height".to_string()));
+    }
+
+    #[test]
+    fn command_line() {
+        let mut config = Config::default();
+        config.set("keys.up=I").unwrap();
+        config.set("keys.down=[\"comma\"]").unwrap_err();
+        config.set("native.win_score = 3").unwrap();
+        assert_eq!(config.keys.paddle.up, vec![Keycode::I]);
+        assert_eq!(config.native.win_score, 3);
+
+        assert_eq!(config.set("native.win_score=100").unwrap_err().to_string(),
+                   "--set 'native.win_score=100': native.win_score: 100 is out of range; expected 1 to 99");
+        assert_eq!(config.set("win_score").unwrap_err().message,
+                   "expected 'section.key=value'");
+        assert_eq!(config.set("audio.volume=1").unwrap_err().message, "unknown section");
+
+        config.set("keys.quit=I").unwrap();
+        assert_eq!(config.validate().unwrap_err().to_string(),
+                   "--set 'keys.quit=I': keys.quit: 'I' is already bound to keys.up");
+    }
+}
@@ -0,0 +1,345 @@
+// controller.rs: game controllers and joysticks, as an alternative to the
+// keyboard.
+//
+// Devices can be plugged in and out at any time; each one gets the lowest
+// player slot that's free when it's plugged in. Devices that SDL recognizes
+// as game controllers use the D-pad, the left stick and the usual buttons;
+// other joysticks can only move the paddle, with their first hat or their
+// vertical axis.
+//
+
+use std::collections::VecDeque;
+
+use sdl2::{
+    Sdl,
+    GameControllerSubsystem,
+    JoystickSubsystem,
+    controller::{Axis, Button, GameController},
+    event::Event,
+    joystick::{HatState, Joystick},
+};
+
+use crate::game::{UserInput, Inputs};
+
+// how far the stick must be pushed, out of 32767, before the paddle moves.
+const DEAD_ZONE:i16 = 8000;
+
+// the vertical axis of a joystick's first stick, by convention.
+const JOYSTICK_Y_AXIS:u8 = 1;
+
+// The button that triggers each action.
+fn action_for_button(button: Button) -> Option<UserInput> {
+    match button {
+        Button::Start => Some(UserInput::Pause),
+        Button::A => Some(UserInput::Serve),
+        Button::Back => Some(UserInput::Restart),
+        _ => None,
+    }
+}
+
+// Kept open for as long as the device is plugged in, since SDL only sends
+// events for open devices.
+#[allow(dead_code)]
+enum Handle {
+    Controller(GameController),
+    Joystick(Joystick),
+}
+
+struct Device {
+    id: i32,
+    slot: usize,
+    handle: Handle,
+    pad: Pad,
+}
+
+/// Every plugged in controller and joystick.
+pub struct Controllers {
+    // None if SDL couldn't initialize them; there are no devices then.
+    subsystems: Option<(GameControllerSubsystem, JoystickSubsystem)>,
+    devices: Vec<Device>,
+    actions: VecDeque<UserInput>,
+}
+
+impl Controllers {
+    pub fn new(sdl_context: &Sdl) -> Controllers {
+        let subsystems = sdl_context.game_controller()
+            .and_then(|controller_sys| {
+                let joystick_sys = sdl_context.joystick()?;
+                Ok((controller_sys, joystick_sys))
+            });
+        let subsystems = match subsystems {
+            Ok(s) => Some(s),
+            Err(e) => {
+                eprintln!("controllers disabled: {}", e);
+                None
+            },
+        };
+        Controllers {
+            subsystems,
+            devices: Vec::new(),
+            actions: VecDeque::new(),
+        }
+    }
+
+    /// Must be called for every event; events from other sources are ignored.
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => self.add_controller(which),
+            Event::JoyDeviceAdded { which, .. } => self.add_joystick(which),
+            Event::ControllerDeviceRemoved { which, .. }
+            | Event::JoyDeviceRemoved { which, .. } => {
+                self.devices.retain(|d| d.id != which);
+            },
+
+            Event::ControllerButtonDown { which, button, .. } => {
+                if let Some(action) = action_for_button(button) {
+                    self.actions.push_back(action);
+                    return;
+                }
+                if let Some(pad) = self.controller_pad(which) {
+                    match button {
+                        Button::DPadUp => pad.press(UserInput::Up),
+                        Button::DPadDown => pad.press(UserInput::Down),
+                        _ => {},
+                    }
+                }
+            },
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(pad) = self.controller_pad(which) {
+                    match button {
+                        Button::DPadUp => pad.up = false,
+                        Button::DPadDown => pad.down = false,
+                        _ => {},
+                    }
+                }
+            },
+            Event::ControllerAxisMotion { which, axis: Axis::LeftY, value, .. } => {
+                if let Some(pad) = self.controller_pad(which) {
+                    pad.move_stick(value);
+                }
+            },
+
+            Event::JoyHatMotion { which, hat_idx: 0, state, .. } => {
+                if let Some(pad) = self.joystick_pad(which) {
+                    let (up, down) = match state {
+                        HatState::Up | HatState::LeftUp | HatState::RightUp => (true, false),
+                        HatState::Down | HatState::LeftDown | HatState::RightDown => (false, true),
+                        _ => (false, false),
+                    };
+                    if up && !pad.up {
+                        pad.press(UserInput::Up);
+                    }
+                    if down && !pad.down {
+                        pad.press(UserInput::Down);
+                    }
+                    pad.up = up;
+                    pad.down = down;
+                }
+            },
+            Event::JoyAxisMotion { which, axis_idx: JOYSTICK_Y_AXIS, value, .. } => {
+                if let Some(pad) = self.joystick_pad(which) {
+                    pad.move_stick(value);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Add the controllers' input for the next tick to the keyboard's.
+    ///
+    /// In two-player mode, the devices in the first two slots move the
+    /// right and left paddles, respectively; otherwise, they all move the
+    /// right one. The keyboard takes precedence, except that actions take
+    /// precedence over movement.
+    ///
+    pub fn sample(&mut self, inputs: &mut Inputs, two_players: bool) {
+        let mut movement = [UserInput::Nothing; 2];
+        for device in &mut self.devices {
+            let slot = if two_players { device.slot } else { 0 };
+            let input = device.pad.sample();
+            if let Some(m) = movement.get_mut(slot) {
+                if *m == UserInput::Nothing {
+                    *m = input;
+                }
+            }
+        }
+
+        let is_movement = matches!(inputs.player,
+                                   UserInput::Up | UserInput::Down | UserInput::Nothing);
+        if is_movement {
+            if let Some(action) = self.actions.pop_front() {
+                inputs.player = action;
+            }
+        }
+        if inputs.player == UserInput::Nothing {
+            inputs.player = movement[0];
+        }
+        if two_players && inputs.second_player == UserInput::Nothing {
+            inputs.second_player = movement[1];
+        }
+    }
+
+    fn add_controller(&mut self, index: u32) {
+        let opened = match self.subsystems {
+            Some((ref controller_sys, _)) => controller_sys.open(index),
+            None => return,
+        };
+        match opened {
+            Ok(controller) => {
+                let id = controller.instance_id();
+                self.add_device(id, Handle::Controller(controller));
+            },
+            Err(e) => eprintln!("failed to open controller {}: {}", index, e),
+        }
+    }
+
+    fn add_joystick(&mut self, index: u32) {
+        let opened = match self.subsystems {
+            // game controllers are also joysticks, and get both events.
+            Some((ref controller_sys, _)) if controller_sys.is_game_controller(index) => return,
+            Some((_, ref joystick_sys)) => joystick_sys.open(index),
+            None => return,
+        };
+        match opened {
+            Ok(joystick) => {
+                let id = joystick.instance_id();
+                self.add_device(id, Handle::Joystick(joystick));
+            },
+            Err(e) => eprintln!("failed to open joystick {}: {}", index, e),
+        }
+    }
+
+    fn add_device(&mut self, id: i32, handle: Handle) {
+        if self.devices.iter().any(|d| d.id == id) {
+            return;
+        }
+        let slot = (0..)
+            .find(|slot| self.devices.iter().all(|d| d.slot != *slot))
+            .unwrap_or(0);
+        self.devices.push(Device {
+            id,
+            slot,
+            handle,
+            pad: Pad::default(),
+        });
+    }
+
+    fn controller_pad(&mut self, id: i32) -> Option<&mut Pad> {
+        self.devices.iter_mut()
+            .find(|d| d.id == id)
+            .and_then(|d| match d.handle {
+                Handle::Controller(_) => Some(&mut d.pad),
+                Handle::Joystick(_) => None,
+            })
+    }
+
+    // Joystick events are also sent for game controllers, which are handled
+    // by their own events instead.
+    fn joystick_pad(&mut self, id: i32) -> Option<&mut Pad> {
+        self.devices.iter_mut()
+            .find(|d| d.id == id)
+            .and_then(|d| match d.handle {
+                Handle::Joystick(_) => Some(&mut d.pad),
+                Handle::Controller(_) => None,
+            })
+    }
+}
+
+/// A device's up and down buttons and its vertical axis.
+///
+/// Like keys, a button that's pressed and released before the next tick
+/// still counts, once, and when both directions are held, the last one
+/// pressed or pushed wins.
+///
+#[derive(Default)]
+struct Pad {
+    up: bool,
+    down: bool,
+    stick: i16,
+    last_pressed: Option<UserInput>,
+    tapped: Option<UserInput>,
+}
+
+impl Pad {
+    fn press(&mut self, input: UserInput) {
+        match input {
+            UserInput::Up => self.up = true,
+            UserInput::Down => self.down = true,
+            _ => return,
+        }
+        self.last_pressed = Some(input);
+        self.tapped = Some(input);
+    }
+
+    // Pushing the stick out of the dead zone counts as pressing that way.
+    fn move_stick(&mut self, value: i16) {
+        if value < -DEAD_ZONE && self.stick >= -DEAD_ZONE {
+            self.last_pressed = Some(UserInput::Up);
+        } else if value > DEAD_ZONE && self.stick <= DEAD_ZONE {
+            self.last_pressed = Some(UserInput::Down);
+        }
+        self.stick = value;
+    }
+
+    fn sample(&mut self) -> UserInput {
+        let tapped = self.tapped.take();
+        // the stick's y axis points down.
+        let up = self.up || self.stick < -DEAD_ZONE;
+        let down = self.down || self.stick > DEAD_ZONE;
+        match (up, down) {
+            (true, false) => UserInput::Up,
+            (false, true) => UserInput::Down,
+            (true, true) => self.last_pressed.unwrap_or(UserInput::Nothing),
+            (false, false) => tapped.unwrap_or(UserInput::Nothing),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stick_has_dead_zone() {
+        let mut pad = Pad {
+            stick: DEAD_ZONE,
+            ..Pad::default()
+        };
+        assert_eq!(pad.sample(), UserInput::Nothing);
+        pad.stick = -DEAD_ZONE - 1;
+        assert_eq!(pad.sample(), UserInput::Up);
+        pad.stick = i16::MAX;
+        assert_eq!(pad.sample(), UserInput::Down);
+    }
+
+    #[test]
+    fn button_tap_counts_once() {
+        let mut pad = Pad::default();
+        pad.press(UserInput::Up);
+        pad.up = false;
+        assert_eq!(pad.sample(), UserInput::Up);
+        assert_eq!(pad.sample(), UserInput::Nothing);
+    }
+
+    #[test]
+    fn last_pressed_wins() {
+        let mut pad = Pad::default();
+        pad.press(UserInput::Up);
+        pad.press(UserInput::Down);
+        assert_eq!(pad.sample(), UserInput::Down);
+        pad.down = false;
+        pad.move_stick(i16::MAX);
+        assert_eq!(pad.sample(), UserInput::Down);
+        pad.press(UserInput::Up);
+        assert_eq!(pad.sample(), UserInput::Up);
+        // releasing the last one goes back to the other.
+        pad.up = false;
+        assert_eq!(pad.sample(), UserInput::Down);
+    }
+
+    #[test]
+    fn actions_are_not_dpad_buttons() {
+        assert_eq!(action_for_button(Button::Start), Some(UserInput::Pause));
+        assert_eq!(action_for_button(Button::DPadUp), None);
+    }
+}
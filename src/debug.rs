@@ -0,0 +1,140 @@
+// debug.rs: a debug mode for the game loop, to look closely at something
+// odd before it scrolls past.
+//
+// The game can be paused and advanced one tick at a time, or slowed down,
+// and the backend's state can be dumped to a file at any time.
+//
+
+use std::{
+    fs,
+    path::PathBuf,
+};
+
+use crate::game::GameState;
+
+pub const DEFAULT_SLOW_MOTION:u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugCommand {
+    /// Stop running ticks, or resume.
+    Pause,
+    /// Run exactly one tick, pausing first if needed.
+    Step,
+    /// Toggle slow motion.
+    SlowMotion,
+    /// Write the state to a file.
+    Dump,
+    /// Save the game to the snapshot file; this one and Load work outside
+    /// the debug mode too.
+    Save,
+    /// Go back to the game in the snapshot file.
+    Load,
+}
+
+pub struct Debugger {
+    paused: bool,
+    // ticks to run while paused.
+    steps: u32,
+    // in slow motion, only one of every slow_motion ticks is run.
+    slow_motion: u32,
+    slow: bool,
+    skipped: u32,
+}
+
+impl Debugger {
+    pub fn new(slow_motion: u32) -> Debugger {
+        Debugger {
+            paused: false,
+            steps: 0,
+            slow_motion: slow_motion.max(1),
+            slow: false,
+            skipped: 0,
+        }
+    }
+
+    /// Handle any command except Dump, Save and Load, which need the state.
+    pub fn command(&mut self, command: DebugCommand) {
+        match command {
+            DebugCommand::Pause => {
+                self.paused = !self.paused;
+                self.steps = 0;
+            },
+            DebugCommand::Step => {
+                self.paused = true;
+                self.steps += 1;
+            },
+            DebugCommand::SlowMotion => {
+                self.slow = !self.slow;
+                self.skipped = 0;
+            },
+            DebugCommand::Dump | DebugCommand::Save | DebugCommand::Load => {},
+        }
+    }
+
+    /// How many ticks to actually run, out of those the clock says are due.
+    pub fn ticks_to_run(&mut self, due: u32) -> u32 {
+        if self.paused {
+            return std::mem::replace(&mut self.steps, 0);
+        }
+        if !self.slow {
+            return due;
+        }
+        let total = self.skipped + due;
+        self.skipped = total % self.slow_motion;
+        total / self.slow_motion
+    }
+
+    /// What to show on screen about the debug mode, if anything.
+    pub fn status(&self, tick: u64) -> Option<String> {
+        if self.paused {
+            Some(format!("DEBUG: PAUSED AT TICK {}", tick))
+        } else if self.slow {
+            Some(format!("DEBUG: SLOW MOTION 1/{}", self.slow_motion))
+        } else {
+            None
+        }
+    }
+
+    /// Write the state to state_<tick>.txt, in the current directory.
+    pub fn dump<S: GameState>(&self, state: &mut S, tick: u64) -> Result<PathBuf, String> {
+        let dump = state.dump_state()
+            .ok_or("this backend can't dump its state")?;
+        let path = PathBuf::from(format!("state_{:06}.txt", tick));
+        fs::write(&path, dump)
+            .map_err(|e| format!("failed to write '{}': {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_runs_one_tick() {
+        let mut debugger = Debugger::new(DEFAULT_SLOW_MOTION);
+        assert_eq!(debugger.ticks_to_run(2), 2);
+        debugger.command(DebugCommand::Step);
+        assert_eq!(debugger.ticks_to_run(3), 1);
+        assert_eq!(debugger.ticks_to_run(3), 0);
+        debugger.command(DebugCommand::Step);
+        debugger.command(DebugCommand::Step);
+        assert_eq!(debugger.ticks_to_run(0), 2);
+        assert_eq!(debugger.status(7), Some("DEBUG: PAUSED AT TICK 7".to_string()));
+        debugger.command(DebugCommand::Pause);
+        assert_eq!(debugger.ticks_to_run(1), 1);
+        assert_eq!(debugger.status(7), None);
+    }
+
+    #[test]
+    fn slow_motion() {
+        let mut debugger = Debugger::new(3);
+        debugger.command(DebugCommand::SlowMotion);
+        let ran: u32 = (0..9).map(|_| debugger.ticks_to_run(1)).sum();
+        assert_eq!(ran, 3);
+        assert_eq!(debugger.ticks_to_run(5), 1);
+        assert_eq!(debugger.ticks_to_run(1), 1);
+        debugger.command(DebugCommand::SlowMotion);
+        assert_eq!(debugger.ticks_to_run(5), 5);
+    }
+}
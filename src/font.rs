@@ -0,0 +1,128 @@
+// font.rs: a tiny built-in bitmap font, so that text can be rendered with
+// nothing but rectangles.
+//
+
+use sdl2::rect::Rect;
+
+pub const GLYPH_WIDTH:i32 = 5;
+pub const GLYPH_HEIGHT:i32 = 7;
+
+// horizontal space between two characters and vertical space between two
+// lines, in font pixels.
+const GLYPH_SPACING:i32 = 1;
+const LINE_SPACING:i32 = 2;
+
+// Each glyph is made of GLYPH_HEIGHT rows, from top to bottom, and each row
+// uses the lowest GLYPH_WIDTH bits, the most significant one being leftmost.
+//
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '\'' => [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '/' => [0b00001, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b10000],
+        // anything we don't know how to draw, including '?' itself.
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+/// Width, in screen pixels, of a single line of text drawn with the given scale.
+pub fn line_width(line: &str, scale: i32) -> i32 {
+    let len = line.chars().count() as i32;
+    if len == 0 {
+        0
+    } else {
+        scale * (len * (GLYPH_WIDTH + GLYPH_SPACING) - GLYPH_SPACING)
+    }
+}
+
+/// Height, in screen pixels, of the given (possibly multi-line) text.
+pub fn text_height(text: &str, scale: i32) -> i32 {
+    let lines = text.lines().count() as i32;
+    scale * (lines * (GLYPH_HEIGHT + LINE_SPACING) - LINE_SPACING)
+}
+
+/// Rectangles needed to draw a single line of text whose upper-left corner
+/// is (x, y), where each pixel of the font is a scale x scale square.
+///
+pub fn line_rects(line: &str, x: i32, y: i32, scale: i32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for (i, c) in line.chars().enumerate() {
+        let glyph_x = x + scale * i as i32 * (GLYPH_WIDTH + GLYPH_SPACING);
+        for (row_num, row) in glyph(c).iter().enumerate() {
+            let row_y = y + scale * row_num as i32;
+
+            // merge adjacent pixels in a row into a single rectangle.
+            let mut col = 0;
+            while col < GLYPH_WIDTH {
+                if row & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    col += 1;
+                    continue;
+                }
+                let run_beg = col;
+                while col < GLYPH_WIDTH && row & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    col += 1;
+                }
+                rects.push(Rect::new(glyph_x + scale * run_beg,
+                                     row_y,
+                                     (scale * (col - run_beg)) as u32,
+                                     scale as u32));
+            }
+        }
+    }
+    rects
+}
+
+/// Rectangles needed to draw the given text centered around (center_x, center_y).
+/// Each line is centered on its own.
+///
+pub fn centered_text_rects(text: &str, center_x: i32, center_y: i32, scale: i32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    let mut y = center_y - text_height(text, scale) / 2;
+    for line in text.lines() {
+        let x = center_x - line_width(line, scale) / 2;
+        rects.append(&mut line_rects(line, x, y, scale));
+        y += scale * (GLYPH_HEIGHT + LINE_SPACING);
+    }
+    rects
+}
@@ -1,91 +1,707 @@
-use sdl2::{
-    EventPump,
-    rect::Rect,
-    video::Window,
-    pixels::Color,
-    event::Event,
-    keyboard::Keycode,
-    render::Canvas,
+// game.rs: what every backend, renderer and input source has in common, and
+// the game loop that ties them together.
+//
+
+use sdl2::rect::Rect;
+
+use std::{
+    fmt,
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::pong::{
-    WINDOW_WIDTH, WINDOW_HEIGHT,
+use crate::{
+    debug::{DebugCommand, Debugger},
+    lambda,
+    snapshot::SnapshotFile,
+    stats::{FrameTimes, Stats},
+    timing::{Timing, TickClock},
 };
 
+// size of each of the font's pixels, in screen pixels.
+pub const TEXT_SCALE:i32 = 4;
+
+// the same, for the performance overlay, which is drawn from the top left
+// corner, OVERLAY_MARGIN pixels away from the edges.
+pub const OVERLAY_SCALE:i32 = 2;
+pub const OVERLAY_MARGIN:i32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UserInput {
     Up,
     Down,
     Nothing,
+    /// Pause the game, or resume it if already paused.
+    Pause,
+    /// Put the ball back into play.
+    Serve,
+    /// Start over from the initial state.
+    Restart,
+    /// The user asked to quit; the game should be over after this.
+    Quit,
 }
 
-pub fn game_init(window_name: &str) -> (Canvas<Window>, EventPump) {
-    let sdl_context = sdl2::init()
-        .expect("failed to initialize SDL");
+impl UserInput {
+    /// Parse an input's name, as used in input scripts and replays.
+    pub fn from_name(name: &str) -> Option<UserInput> {
+        match name {
+            "up" => Some(UserInput::Up),
+            "down" => Some(UserInput::Down),
+            "nothing" | "-" => Some(UserInput::Nothing),
+            "pause" => Some(UserInput::Pause),
+            "serve" => Some(UserInput::Serve),
+            "restart" => Some(UserInput::Restart),
+            "quit" => Some(UserInput::Quit),
+            _ => None,
+        }
+    }
 
-    let video_sys = sdl_context.video()
-        .expect("failed to initialize video subsystem");
+    /// The input's name, as accepted by from_name.
+    pub fn name(self) -> &'static str {
+        match self {
+            UserInput::Up => "up",
+            UserInput::Down => "down",
+            UserInput::Nothing => "nothing",
+            UserInput::Pause => "pause",
+            UserInput::Serve => "serve",
+            UserInput::Restart => "restart",
+            UserInput::Quit => "quit",
+        }
+    }
+}
 
-    let window = video_sys.window(window_name, WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
-        .position_centered()
-        .build()
-        .expect("failed to create window");
+/// The inputs for a single tick, one for each player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Inputs {
+    /// The right paddle's player, who's also the one who pauses, quits, etc.
+    pub player: UserInput,
+    /// The left paddle's player, in two-player mode. Otherwise, the CPU
+    /// controls the left paddle, and this is always Nothing.
+    pub second_player: UserInput,
+}
 
-    let canvas = window.into_canvas()
-        .present_vsync()
-        .build()
-        .expect("failed to create canvas / renderer");
+/// How well the CPU plays.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
 
-    let event_pump = sdl_context.event_pump()
-        .expect("failed to get event pump");
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            "insane" => Some(Difficulty::Insane),
+            _ => None,
+        }
+    }
 
-    (canvas, event_pump)
+    /// The difficulty's name, as accepted by from_name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Insane => "insane",
+        }
+    }
+}
+
+/// Something that happened during a tick, for sound effects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// The ball bounced off a paddle.
+    PaddleHit,
+    /// The ball bounced off the top or bottom of the screen.
+    WallBounce,
+    /// Someone scored.
+    Score,
+}
+
+impl GameEvent {
+    /// The event for a tag in the lambda backend's list of events, if it's
+    /// one we know.
+    pub fn from_tag(tag: i32) -> Option<GameEvent> {
+        match tag {
+            0 => Some(GameEvent::PaddleHit),
+            1 => Some(GameEvent::WallBounce),
+            2 => Some(GameEvent::Score),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameEvent> {
+        match name {
+            "paddle_hit" => Some(GameEvent::PaddleHit),
+            "wall_bounce" => Some(GameEvent::WallBounce),
+            "score" => Some(GameEvent::Score),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameEvent::PaddleHit => "paddle_hit",
+            GameEvent::WallBounce => "wall_bounce",
+            GameEvent::Score => "score",
+        }
+    }
+}
+
+/// Why the game had to stop.
+#[derive(Debug)]
+pub enum Error {
+    /// The backend failed; only the lambda backend can.
+    Backend(lambda::Error),
+    /// A frame couldn't be drawn, or written out.
+    Render(String),
+    /// The other player's game went out of sync, or the other side of a
+    /// connection sent nonsense.
+    Network(String),
+}
+
+impl From<lambda::Error> for Error {
+    fn from(error: lambda::Error) -> Error {
+        Error::Backend(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Backend(e) => write!(f, "{}", e),
+            Error::Render(e) => write!(f, "failed to draw a frame: {}", e),
+            Error::Network(e) => write!(f, "{}", e),
+        }
+    }
 }
 
 pub trait GameState {
-    fn game_over(&mut self) -> bool;
-    fn update(&mut self, input: UserInput);
-    fn get_rects(&mut self) -> Vec<Rect>;
+    fn game_over(&mut self) -> Result<bool, Error>;
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error>;
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error>;
+
+    /// Text to be shown centered on the screen, if any.
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    /// Who won and the final score, shown once the game is over, if known.
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    /// What happened during the last update.
+    fn events(&mut self) -> Vec<GameEvent> {
+        Vec::new()
+    }
+
+    /// The left and right paddles' scores, if known.
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        Ok(None)
+    }
+
+    /// The state in a form that's useful for debugging, if there's one.
+    fn dump_state(&mut self) -> Option<String> {
+        None
+    }
+
+    /// The state as text that load_state can read back, for a snapshot, if
+    /// the backend can be saved.
+    fn save_state(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Go back to a state from save_state.
+    fn load_state(&mut self, _saved: &str) -> Result<(), String> {
+        Err("this game can't be loaded".to_string())
+    }
+}
+
+/// Who won a game that ended with the given scores, and the score, in the
+/// same order as on the screen.
+///
+pub fn result_text(left: usize, right: usize, two_players: bool) -> String {
+    let (right_wins, left_wins) = if two_players {
+        ("RIGHT PLAYER WINS", "LEFT PLAYER WINS")
+    } else {
+        ("YOU WIN", "CPU WINS")
+    };
+    let winner = if right > left {
+        right_wins
+    } else if left > right {
+        left_wins
+    } else {
+        "DRAW"
+    };
+    format!("{}  {} - {}", winner, left, right)
+}
+
+/// What the renderer draws: the state's rectangles and text after a tick.
+pub struct Frame<'a> {
+    /// How many ticks have been run so far.
+    pub tick: u64,
+    pub rects: &'a [Rect],
+    pub text: Option<&'a str>,
+    /// What happened during the ticks since the last frame, each with the
+    /// tick it happened on.
+    pub events: &'a [(u64, GameEvent)],
+    /// The inputs of the ticks since the last frame.
+    pub inputs: &'a [Inputs],
+    /// The left and right paddles' scores, if known and wanted; see
+    /// Renderer::wants_scores.
+    pub scores: Option<(usize, usize)>,
+    /// Performance figures to be shown in a corner, if any.
+    pub overlay: Option<&'a str>,
+}
+
+/// Where frames are drawn: a window, the terminal, a file...
+pub trait Renderer {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String>;
+
+    /// Whether the frames' scores are used. Getting them can cost the
+    /// backend an evaluation per frame, so they're only got for renderers
+    /// that want them.
+    ///
+    fn wants_scores(&self) -> bool {
+        false
+    }
+}
+
+/// Where each tick's inputs come from: the keyboard, a script...
+pub trait InputSource {
+    /// Handle whatever happened since the last frame. Returns false if the
+    /// user closed the game, e.g. by closing its window.
+    ///
+    fn poll(&mut self) -> bool {
+        true
+    }
+
+    fn next_inputs(&mut self) -> Inputs;
+
+    /// The next debug command the user gave since the last frame, if any.
+    fn debug_command(&mut self) -> Option<DebugCommand> {
+        None
+    }
 }
 
-pub fn game_loop<S: GameState>(mut canvas: Canvas<Window>,
-                               mut event_pump: EventPump,
-                               mut state: S)
+// Draw to both, e.g. to save frames while printing them.
+impl<A: Renderer, B: Renderer> Renderer for (A, B) {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        self.0.draw(frame)?;
+        self.1.draw(frame)
+    }
+
+    fn wants_scores(&self) -> bool {
+        self.0.wants_scores() || self.1.wants_scores()
+    }
+}
+
+impl<R: Renderer> Renderer for Option<R> {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        match self {
+            Some(renderer) => renderer.draw(frame),
+            None => Ok(()),
+        }
+    }
+
+    fn wants_scores(&self) -> bool {
+        self.as_ref().is_some_and(Renderer::wants_scores)
+    }
+}
+
+/// How fast the game loop runs.
+#[derive(Clone, Copy, Debug)]
+pub enum Pace {
+    /// At a fixed rate in real time, given by timing, independently of how
+    /// often frames are presented.
+    RealTime(Timing),
+    /// As fast as possible, for the given number of ticks, with a frame
+    /// after each one.
+    Ticks(u64),
+}
+
+/// Run the game until the user quits, or, when not in real time, until it's
+/// over or the ticks run out. In real time, the game being over shows the
+/// result until the user asks for a rematch or quits. If stats are given,
+/// every frame is timed, and if a debugger is given, the input's debug
+/// commands are followed. If a snapshot file is given, the game can be saved
+/// to it and loaded from it. The first error from the state or the renderer
+/// stops the loop.
+///
+pub fn game_loop<S, R, I>(state: &mut S,
+                          renderer: &mut R,
+                          input: &mut I,
+                          pace: Pace,
+                          mut stats: Option<&mut Stats>,
+                          mut debugger: Option<&mut Debugger>,
+                          snapshots: Option<&SnapshotFile>) -> Result<(), Error>
+    where S: GameState, R: Renderer, I: InputSource
 {
-    let mut user_input;
-    'game_loop: loop {
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-
-        user_input = UserInput::Nothing;
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} => break 'game_loop,
-
-                Event::KeyDown { keycode, .. } => match keycode {
-                    Some(Keycode::Q) => break 'game_loop,
-                    Some(Keycode::Up) | Some(Keycode::K) => {
-                        user_input = UserInput::Up;
-                    },
-                    Some(Keycode::Down) | Some(Keycode::J) => {
-                        user_input = UserInput::Down;
-                    },
-                    _ => {},
+    let mut clock = match pace {
+        Pace::RealTime(ref timing) => Some(TickClock::new(timing)),
+        Pace::Ticks(_) => None,
+    };
+    let mut last_frame = Instant::now();
+    let mut tick = 0;
+    loop {
+        let frame_start = Instant::now();
+        let mut times = FrameTimes::default();
+        if !input.poll() {
+            return Ok(());
+        }
+
+        let ticks = match clock {
+            Some(ref mut clock) => {
+                let ticks = clock.advance(frame_start - last_frame);
+                last_frame = frame_start;
+                ticks
+            },
+            None => 1,
+        };
+        while let Some(command) = input.debug_command() {
+            match (&mut debugger, command) {
+                (Some(debugger), DebugCommand::Dump) => match debugger.dump(state, tick) {
+                    Ok(path) => eprintln!("state at tick {} dumped to '{}'", tick, path.display()),
+                    Err(e) => eprintln!("failed to dump the state: {}", e),
+                },
+                (_, DebugCommand::Save) | (_, DebugCommand::Load) => {
+                    if let Some(snapshots) = snapshots {
+                        snapshot_command(state, snapshots, command);
+                    }
+                },
+                (Some(debugger), command) => debugger.command(command),
+                (None, _) => {},
+            }
+        }
+        let ticks = match debugger {
+            Some(ref mut debugger) => debugger.ticks_to_run(ticks),
+            None => ticks,
+        };
+        let mut over = false;
+        // whether the game is over because the user quit.
+        let mut quit = false;
+        let mut events = Vec::new();
+        let mut tick_inputs = Vec::new();
+        for _ in 0..ticks {
+            let update_start = Instant::now();
+            let inputs = input.next_inputs();
+            quit |= inputs.player == UserInput::Quit;
+            tick_inputs.push(inputs);
+            state.update(inputs)?;
+            events.extend(state.events().into_iter().map(|event| (tick + 1, event)));
+            let game_over_start = Instant::now();
+            over = state.game_over()?;
+            times.update += game_over_start - update_start;
+            times.game_over += game_over_start.elapsed();
+            times.ticks += 1;
+            tick += 1;
+            if over {
+                break;
+            }
+        }
+
+        let get_rects_start = Instant::now();
+        let rects = state.get_rects()?;
+        let text = state.get_text()?;
+        let scores = scores(state, renderer)?;
+        let stats_overlay = stats.as_ref().and_then(|stats| stats.overlay());
+        let debug_status = debugger.as_ref().and_then(|debugger| debugger.status(tick));
+        let overlay = match (stats_overlay, debug_status) {
+            (Some(stats), Some(debug)) => Some(format!("{}  {}", debug, stats)),
+            (stats, debug) => debug.or(stats),
+        };
+        let present_start = Instant::now();
+        times.get_rects = present_start - get_rects_start;
+        renderer.draw(&Frame {
+            tick,
+            rects: &rects,
+            text: text.as_deref(),
+            events: &events,
+            inputs: &tick_inputs,
+            scores,
+            overlay: overlay.as_deref(),
+        }).map_err(Error::Render)?;
+        times.present = present_start.elapsed();
+
+        let done = match pace {
+            _ if over => true,
+            Pace::Ticks(max_ticks) => tick >= max_ticks,
+            Pace::RealTime(timing) => {
+                if let (false, Some(ref clock)) = (timing.vsync, &clock) {
+                    thread::sleep(clock.until_next_tick());
+                }
+                false
+            },
+        };
+        if let Some(ref mut stats) = stats {
+            times.frame = frame_start.elapsed();
+            stats.record(times);
+        }
+        if done {
+            let rematch = match pace {
+                Pace::RealTime(ref timing) if over && !quit => {
+                    game_over_screen(state, renderer, input, tick, timing)?
                 },
-                _ => {},
+                _ => false,
+            };
+            if !rematch {
+                return Ok(());
+            }
+            // the time spent on the game over screen isn't owed to the clock.
+            last_frame = Instant::now();
+        }
+    }
+}
+
+// Save the game to the snapshot file, or load it from there, saying how it
+// went.
+fn snapshot_command<S: GameState>(state: &mut S, snapshots: &SnapshotFile, command: DebugCommand) {
+    let path = snapshots.path().display();
+    if command == DebugCommand::Save {
+        match snapshots.save(state) {
+            Ok(()) => eprintln!("game saved to '{}'", path),
+            Err(e) => eprintln!("failed to save the game: {}", e),
+        }
+    } else {
+        match snapshots.load(state) {
+            Ok(()) => eprintln!("game loaded from '{}'", path),
+            Err(e) => eprintln!("failed to load the game: {}", e),
+        }
+    }
+}
+
+// Show the final state with the result until the user restarts, which
+// returns true once the game started over, or quits.
+fn game_over_screen<S, R, I>(state: &mut S,
+                             renderer: &mut R,
+                             input: &mut I,
+                             tick: u64,
+                             timing: &Timing) -> Result<bool, Error>
+    where S: GameState, R: Renderer, I: InputSource
+{
+    let rects = state.get_rects()?;
+    let scores = scores(state, renderer)?;
+    let mut text = game_over_text(state)?;
+    let mut restarting = false;
+    loop {
+        if !input.poll() {
+            return Ok(false);
+        }
+        let inputs = input.next_inputs();
+        match inputs.player {
+            UserInput::Restart if !restarting => {
+                // every backend starts over on a restart, and this way
+                // it's also recorded like any other input.
+                state.update(inputs)?;
+                restarting = true;
+            },
+            UserInput::Quit => return Ok(false),
+            _ => {},
+        }
+        if restarting {
+            // a network game only starts over once the other player asks
+            // for a rematch too.
+            if !state.game_over()? {
+                return Ok(true);
+            }
+            text = game_over_text(state)?;
+        }
+        renderer.draw(&Frame {
+            tick,
+            rects: &rects,
+            text: Some(&text),
+            events: &[],
+            inputs: &[],
+            scores,
+            overlay: None,
+        }).map_err(Error::Render)?;
+        if !timing.vsync {
+            thread::sleep(Duration::from_secs(1) / timing.tick_rate.max(1));
+        }
+    }
+}
+
+// The scores for a frame, if the renderer wants them.
+fn scores<S, R>(state: &mut S, renderer: &R) -> Result<Option<(usize, usize)>, Error>
+    where S: GameState, R: Renderer
+{
+    if renderer.wants_scores() {
+        state.scores()
+    } else {
+        Ok(None)
+    }
+}
+
+fn game_over_text<S: GameState>(state: &mut S) -> Result<String, Error> {
+    Ok(format!("{}\nR: REMATCH  Q: QUIT",
+               state.result()?.unwrap_or_else(|| "GAME OVER".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless::Script, pong};
+
+    struct Recorder {
+        frames: Vec<(u64, usize)>,
+    }
+
+    impl Renderer for Recorder {
+        fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+            self.frames.push((frame.tick, frame.rects.len()));
+            Ok(())
+        }
+    }
+
+    struct Pressing(UserInput);
+
+    impl InputSource for Pressing {
+        fn next_inputs(&mut self) -> Inputs {
+            Inputs {
+                player: self.0,
+                second_player: UserInput::Nothing,
+            }
+        }
+    }
+
+    #[test]
+    fn frame_after_each_tick() {
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut renderer = (Recorder { frames: Vec::new() }, None::<Recorder>);
+        game_loop(&mut state, &mut renderer, &mut Pressing(UserInput::Up),
+                  Pace::Ticks(3), None, None, None).unwrap();
+        let ticks: Vec<u64> = renderer.0.frames.iter().map(|f| f.0).collect();
+        assert_eq!(ticks, [1, 2, 3]);
+    }
+
+    #[test]
+    fn stops_when_game_is_over() {
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut renderer = Recorder { frames: Vec::new() };
+        game_loop(&mut state, &mut renderer, &mut Pressing(UserInput::Quit),
+                  Pace::Ticks(10), None, None, None).unwrap();
+        assert_eq!(renderer.frames.len(), 1);
+    }
+
+    #[test]
+    fn scores_only_when_wanted() {
+        struct Scores(Vec<Option<(usize, usize)>>, bool);
+        impl Renderer for Scores {
+            fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+                self.0.push(frame.scores);
+                Ok(())
+            }
+            fn wants_scores(&self) -> bool {
+                self.1
             }
         }
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut renderer = (Recorder { frames: Vec::new() }, Some(Scores(Vec::new(), false)));
+        game_loop(&mut state, &mut renderer, &mut Pressing(UserInput::Nothing),
+                  Pace::Ticks(2), None, None, None).unwrap();
+        assert_eq!(renderer.1.as_ref().unwrap().0, [None, None]);
 
-        state.update(user_input);
+        renderer.1 = Some(Scores(Vec::new(), true));
+        game_loop(&mut state, &mut renderer, &mut Pressing(UserInput::Nothing),
+                  Pace::Ticks(1), None, None, None).unwrap();
+        assert_eq!(renderer.1.unwrap().0, [Some((0, 0))]);
+    }
 
-        if state.game_over() {
-            break 'game_loop;
+    #[test]
+    fn renderer_errors_stop_the_loop() {
+        struct Failing;
+        impl Renderer for Failing {
+            fn draw(&mut self, _: &Frame) -> Result<(), String> {
+                Err("no display".to_string())
+            }
+        }
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let result = game_loop(&mut state, &mut Failing, &mut Pressing(UserInput::Nothing),
+                               Pace::Ticks(10), None, None, None);
+        match result {
+            Err(Error::Render(e)) => assert_eq!(e, "no display"),
+            other => panic!("expected a render error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn events_reach_the_renderer() {
+        struct Listener(Vec<(u64, GameEvent)>);
+        impl Renderer for Listener {
+            fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+                self.0.extend_from_slice(frame.events);
+                Ok(())
+            }
         }
+        // the ball starts going down, and bounces off the bottom of the screen.
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut listener = Listener(Vec::new());
+        game_loop(&mut state, &mut listener, &mut Pressing(UserInput::Nothing),
+                  Pace::Ticks(400), None, None, None).unwrap();
+        assert_eq!(listener.0.first().map(|e| e.1), Some(GameEvent::WallBounce));
+    }
 
-        canvas.set_draw_color(Color::RGB(0xff, 0xff, 0xff));
-        for rect in state.get_rects() {
-            canvas.fill_rect(Some(rect.clone())).expect("failed to draw rectangle");
+    #[test]
+    fn rematch_after_game_over() {
+        // a game that's over after every tick, until restarted.
+        struct Short {
+            over: bool,
+            restarts: u32,
+        }
+        impl GameState for Short {
+            fn game_over(&mut self) -> Result<bool, Error> {
+                Ok(self.over)
+            }
+            fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+                if inputs.player == UserInput::Restart {
+                    self.restarts += 1;
+                    self.over = false;
+                } else {
+                    self.over = true;
+                }
+                Ok(())
+            }
+            fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
+                Ok(Vec::new())
+            }
         }
+        struct Texts(Vec<Option<String>>);
+        impl Renderer for Texts {
+            fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+                self.0.push(frame.text.map(str::to_string));
+                Ok(())
+            }
+        }
+
+        let mut state = Short { over: false, restarts: 0 };
+        let mut texts = Texts(Vec::new());
+        let mut script = Script::parse("1 -\n1 -\n1 restart\n1 -\n1 -\n1 quit").unwrap();
+        let timing = Timing {
+            tick_rate: 1000,
+            vsync: false,
+            ..Timing::default()
+        };
+        game_loop(&mut state, &mut texts, &mut script, Pace::RealTime(timing), None, None, None).unwrap();
+        assert_eq!(state.restarts, 1);
+        // frames without ticks come and go with the clock.
+        texts.0.dedup();
+        let game_over = Some("GAME OVER\nR: REMATCH  Q: QUIT".to_string());
+        assert_eq!(texts.0, [None, game_over.clone(), None, game_over]);
+    }
 
-        canvas.present();
+    #[test]
+    fn frames_are_timed() {
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut renderer = None::<Recorder>;
+        let mut stats = Stats::new(false);
+        game_loop(&mut state, &mut renderer, &mut Pressing(UserInput::Nothing),
+                  Pace::Ticks(4), Some(&mut stats), None, None).unwrap();
+        assert!(stats.summary().starts_with("4 frames, 4 ticks\n"));
     }
 }
@@ -0,0 +1,276 @@
+// headless.rs: runs a game without a window, for automated runs.
+//
+// The inputs come from a script rather than from the keyboard, and after
+// every tick the state's rectangles are written out as a line of JSON, so
+// that runs can be compared with each other. Frames can also be saved as
+// images, and sounds as audio. SDL is never initialized.
+//
+
+use std::{
+    fmt::Write as _,
+    io::Write,
+};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    game::{self, Frame, GameState, InputSource, Inputs, Pace, Renderer, UserInput},
+    stats::Stats,
+};
+
+/// A fixed sequence of inputs, one per tick.
+///
+/// Scripts are text, with one step per line: how many ticks it lasts, the
+/// first player's input and, optionally, the second player's, e.g.
+///
+/// ```text
+/// # wait a second, then go up for half a second.
+/// 60 nothing
+/// 30 up down
+/// 1 pause
+/// ```
+///
+/// Input names are those accepted by `UserInput::from_name`. Everything
+/// after a `#` is ignored. Once the script is over, the input is Nothing.
+///
+pub struct Script {
+    steps: Vec<(u64, Inputs)>,
+    // the current step and how many ticks are left of it.
+    step: usize,
+    ticks_done: u64,
+}
+
+impl Script {
+    /// A script with no steps, in which nothing is ever pressed.
+    pub fn empty() -> Script {
+        Script {
+            steps: Vec::new(),
+            step: 0,
+            ticks_done: 0,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Script, String> {
+        Script::parse_lines(text.lines().enumerate())
+    }
+
+    /// Parse the script's lines, each with its index in the whole file, for
+    /// scripts that are only part of a file.
+    ///
+    pub fn parse_lines<'a, I>(lines: I) -> Result<Script, String>
+        where I: Iterator<Item = (usize, &'a str)>
+    {
+        let mut steps = Vec::new();
+        for (line_num, line) in lines {
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let count = match words.next() {
+                None => continue,
+                Some(count) => count,
+            };
+            let error = |msg: &str| format!("line {}: {}", line_num + 1, msg);
+
+            let count = count.parse::<u64>()
+                .map_err(|_| error("expected a number of ticks"))?;
+            let input = |word: Option<&str>| match word {
+                None => Ok(UserInput::Nothing),
+                Some(name) => UserInput::from_name(name)
+                    .ok_or_else(|| error(&format!("unknown input '{}'", name))),
+            };
+            let player = match words.next() {
+                None => return Err(error("expected an input")),
+                word => input(word)?,
+            };
+            let second_player = input(words.next())?;
+            if words.next().is_some() {
+                return Err(error("too many inputs"));
+            }
+            steps.push((count, Inputs { player, second_player }));
+        }
+        Ok(Script {
+            steps,
+            step: 0,
+            ticks_done: 0,
+        })
+    }
+
+    /// The inputs for the next tick, or None if the script is over.
+    pub fn next_step(&mut self) -> Option<Inputs> {
+        while let Some(&(count, inputs)) = self.steps.get(self.step) {
+            if self.ticks_done < count {
+                self.ticks_done += 1;
+                return Some(inputs);
+            }
+            self.step += 1;
+            self.ticks_done = 0;
+        }
+        None
+    }
+
+    /// The inputs for the next tick.
+    pub fn next_inputs(&mut self) -> Inputs {
+        self.next_step().unwrap_or(Inputs {
+            player: UserInput::Nothing,
+            second_player: UserInput::Nothing,
+        })
+    }
+}
+
+impl InputSource for Script {
+    fn next_inputs(&mut self) -> Inputs {
+        Script::next_inputs(self)
+    }
+}
+
+/// Writes a line of JSON for every frame.
+pub struct JsonLines<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(out: W) -> JsonLines<W> {
+        JsonLines { out }
+    }
+}
+
+impl<W: Write> Renderer for JsonLines<W> {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        writeln!(self.out, "{}", json_line(frame.tick, frame.rects, frame.text))
+            .and_then(|_| self.out.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Run the game for the given number of ticks, or until it's over, writing
+/// a line of JSON to out after every tick, and drawing every frame to
+/// outputs too, e.g. to save it as an image.
+///
+pub fn run<S, W, R>(state: &mut S,
+                    script: &mut Script,
+                    ticks: u64,
+                    out: W,
+                    outputs: R,
+                    stats: Option<&mut Stats>) -> Result<(), game::Error>
+    where S: GameState, W: Write, R: Renderer
+{
+    if ticks == 0 {
+        return Ok(());
+    }
+    let mut renderer = (JsonLines::new(out), outputs);
+    game::game_loop(state, &mut renderer, script, Pace::Ticks(ticks), stats, None, None)
+}
+
+// e.g. {"tick":1,"rects":[[0,10,20,30]],"text":null}
+pub fn json_line(tick: u64, rects: &[Rect], text: Option<&str>) -> String {
+    let mut line = format!("{{\"tick\":{},\"rects\":[", tick);
+    for (i, rect) in rects.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "[{},{},{},{}]", rect.x(), rect.y(), rect.width(), rect.height());
+    }
+    line.push_str("],\"text\":");
+    match text {
+        None => line.push_str("null"),
+        Some(text) => push_json_string(&mut line, text),
+    }
+    line.push('}');
+    line
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::Difficulty, pong, raster::FrameExport};
+
+    #[test]
+    fn script_steps() {
+        let mut script = Script::parse("# comment\n2 up\n\n1 serve down # serve\n").unwrap();
+        let inputs: Vec<Inputs> = (0..4).map(|_| script.next_inputs()).collect();
+        assert_eq!(inputs[0].player, UserInput::Up);
+        assert_eq!(inputs[1].player, UserInput::Up);
+        assert_eq!(inputs[2], Inputs {
+            player: UserInput::Serve,
+            second_player: UserInput::Down,
+        });
+        assert_eq!(inputs[3].player, UserInput::Nothing);
+    }
+
+    #[test]
+    fn script_errors() {
+        assert!(Script::parse("up").is_err());
+        assert!(Script::parse("3").is_err());
+        assert!(Script::parse("3 jump").is_err());
+        assert!(Script::parse("3 up down up").is_err());
+        assert_eq!(Script::parse("1 up\nx up").err(),
+                   Some("line 2: expected a number of ticks".to_string()));
+    }
+
+    #[test]
+    fn json_lines() {
+        let rects = [Rect::new(1, -2, 3, 4), Rect::new(5, 6, 7, 8)];
+        assert_eq!(json_line(3, &rects, None),
+                   r#"{"tick":3,"rects":[[1,-2,3,4],[5,6,7,8]],"text":null}"#);
+        assert_eq!(json_line(1, &[], Some("A \"B\"\n")),
+                   r#"{"tick":1,"rects":[],"text":"A \"B\"\n"}"#);
+    }
+
+    #[test]
+    fn runs_native_backend() {
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut script = Script::parse("5 up").unwrap();
+        let mut out = Vec::new();
+        run(&mut state, &mut script, 10, &mut out, None::<FrameExport>, None).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert!(lines[9].starts_with(r#"{"tick":10,"rects":[["#));
+
+        // the same inputs always give the same output.
+        let mut again = Vec::new();
+        let mut script = Script::parse("5 up").unwrap();
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        run(&mut state, &mut script, 10, &mut again, None::<FrameExport>, None).unwrap();
+        assert_eq!(out.as_bytes(), &again[..]);
+    }
+
+    #[test]
+    fn stops_when_game_is_over() {
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut script = Script::parse("1 - \n1 quit").unwrap();
+        let mut out = Vec::new();
+        run(&mut state, &mut script, 10, &mut out, None::<FrameExport>, None).unwrap();
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+
+    #[test]
+    fn plays_until_game_over() {
+        // the player never moves, so the CPU scores until it wins.
+        let mut state = pong::State::new(false, pong::Tunables::default(), Difficulty::Hard);
+        let mut out = Vec::new();
+        run(&mut state, &mut Script::empty(), 1_000_000, &mut out, None::<FrameExport>, None).unwrap();
+        assert!(state.game_over().unwrap());
+        assert_eq!(state.scores().unwrap(), Some((pong::Tunables::default().win_score, 0)));
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().count() < 1_000_000);
+        assert!(out.ends_with("\"text\":null}\n"));
+    }
+}
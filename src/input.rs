@@ -0,0 +1,351 @@
+// input.rs: turns the keyboard into one UserInput per tick.
+//
+// Rather than reacting to key press events, which depend on the OS's key
+// repeat delay and rate, the keyboard state is sampled every tick, so holding
+// a key moves the paddle smoothly from the very first tick.
+//
+// Actions such as pausing are different: each key press is an action that
+// takes up a whole tick of its own.
+//
+
+use std::collections::VecDeque;
+
+use sdl2::{
+    EventPump,
+    keyboard::{Keycode, Scancode},
+};
+
+use crate::{
+    debug::DebugCommand,
+    game::{UserInput, Inputs},
+};
+
+/// Keys that move a paddle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaddleKeys {
+    pub up: Vec<Keycode>,
+    pub down: Vec<Keycode>,
+}
+
+/// What every key does; each paddle direction and action can have several
+/// keys. Actions always belong to the first player.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    pub paddle: PaddleKeys,
+    // for the second player, who uses the left paddle.
+    pub second_paddle: PaddleKeys,
+    pub pause: Vec<Keycode>,
+    pub serve: Vec<Keycode>,
+    pub restart: Vec<Keycode>,
+    pub quit: Vec<Keycode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            paddle: PaddleKeys {
+                up: vec![Keycode::Up, Keycode::K],
+                down: vec![Keycode::Down, Keycode::J],
+            },
+            second_paddle: PaddleKeys {
+                up: vec![Keycode::W],
+                down: vec![Keycode::S],
+            },
+            pause: vec![Keycode::P],
+            serve: vec![Keycode::Space],
+            restart: vec![Keycode::R],
+            quit: vec![Keycode::Q, Keycode::Escape],
+        }
+    }
+}
+
+impl KeyBindings {
+    // The action a key triggers, if any.
+    fn action_for_key(&self, keycode: Keycode) -> Option<UserInput> {
+        let actions = [
+            (&self.pause, UserInput::Pause),
+            (&self.serve, UserInput::Serve),
+            (&self.restart, UserInput::Restart),
+            (&self.quit, UserInput::Quit),
+        ];
+        actions.iter()
+            .find(|(keys, _)| keys.contains(&keycode))
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Toggles fullscreen in the window.
+pub const FULLSCREEN_KEY:Keycode = Keycode::F11;
+
+/// Whether the key always does something else, so it can't be bound.
+pub fn is_reserved(keycode: Keycode) -> bool {
+    keycode == FULLSCREEN_KEY || debug_command_for_key(keycode).is_some()
+}
+
+// Keys with a name rather than a character, by name.
+const NAMED_KEYS:&[(&str, Keycode)] = &[
+    ("up", Keycode::Up),
+    ("down", Keycode::Down),
+    ("left", Keycode::Left),
+    ("right", Keycode::Right),
+    ("space", Keycode::Space),
+    ("escape", Keycode::Escape),
+    ("return", Keycode::Return),
+    ("enter", Keycode::Return),
+    ("tab", Keycode::Tab),
+    ("backspace", Keycode::Backspace),
+    ("f1", Keycode::F1),
+    ("f2", Keycode::F2),
+    ("f3", Keycode::F3),
+    ("f4", Keycode::F4),
+    ("f5", Keycode::F5),
+    ("f6", Keycode::F6),
+    ("f7", Keycode::F7),
+    ("f8", Keycode::F8),
+    ("f9", Keycode::F9),
+    ("f10", Keycode::F10),
+    ("f11", Keycode::F11),
+    ("f12", Keycode::F12),
+];
+
+/// The key's name, as keycode_from_name takes it.
+pub fn keycode_name(keycode: Keycode) -> String {
+    match NAMED_KEYS.iter().find(|(_, named)| *named == keycode) {
+        Some((name, _)) => {
+            let mut name = name.to_string();
+            name[..1].make_ascii_uppercase();
+            name
+        },
+        None => ((keycode as i32) as u8 as char).to_ascii_uppercase().to_string(),
+    }
+}
+
+/// The key with the given name, which is either one from NAMED_KEYS or a
+/// single letter, digit or punctuation character; case doesn't matter.
+///
+pub fn keycode_from_name(name: &str) -> Option<Keycode> {
+    let lower = name.to_ascii_lowercase();
+    if let Some((_, keycode)) = NAMED_KEYS.iter().find(|(key_name, _)| *key_name == lower) {
+        return Some(*keycode);
+    }
+    let mut chars = lower.chars();
+    match (chars.next(), chars.next()) {
+        // SDL's keycodes for characters are the characters themselves.
+        (Some(c), None) if c.is_ascii_graphic() => Keycode::from_i32(c as i32),
+        _ => None,
+    }
+}
+
+/// The key that gives each debug command.
+pub fn debug_command_for_key(keycode: Keycode) -> Option<DebugCommand> {
+    match keycode {
+        Keycode::F5 => Some(DebugCommand::Pause),
+        Keycode::F6 => Some(DebugCommand::Step),
+        Keycode::F7 => Some(DebugCommand::SlowMotion),
+        Keycode::F8 => Some(DebugCommand::Dump),
+        Keycode::F9 => Some(DebugCommand::Save),
+        Keycode::F10 => Some(DebugCommand::Load),
+        _ => None,
+    }
+}
+
+/// All of the keyboard's input: the paddles' keys and the actions.
+pub struct Keyboard {
+    paddle: HeldKeys,
+    second_paddle: Option<HeldKeys>,
+    bindings: KeyBindings,
+    actions: VecDeque<UserInput>,
+}
+
+impl Keyboard {
+    pub fn new(two_players: bool, bindings: &KeyBindings) -> Keyboard {
+        Keyboard {
+            paddle: HeldKeys::new(bindings.paddle.clone()),
+            second_paddle: if two_players {
+                Some(HeldKeys::new(bindings.second_paddle.clone()))
+            } else {
+                None
+            },
+            bindings: bindings.clone(),
+            actions: VecDeque::new(),
+        }
+    }
+
+    /// Must be called for every key press event that isn't a repeat.
+    pub fn key_down(&mut self, keycode: Keycode) {
+        if let Some(action) = self.bindings.action_for_key(keycode) {
+            self.actions.push_back(action);
+            return;
+        }
+        self.paddle.key_down(keycode);
+        if let Some(ref mut second_paddle) = self.second_paddle {
+            second_paddle.key_down(keycode);
+        }
+    }
+
+    /// The inputs for the next tick; the first player's pending actions come
+    /// before their paddle's movement.
+    ///
+    pub fn sample(&mut self, event_pump: &EventPump) -> Inputs {
+        let player = match self.actions.pop_front() {
+            Some(action) => action,
+            None => self.paddle.sample(event_pump),
+        };
+        let second_player = match self.second_paddle {
+            Some(ref mut second_paddle) => second_paddle.sample(event_pump),
+            None => UserInput::Nothing,
+        };
+        Inputs {
+            player,
+            second_player,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Vertical {
+    Up,
+    Down,
+}
+
+/// Keeps track of a paddle's keys between ticks.
+///
+/// When both the up and down keys are held, the one pressed last wins.
+/// A key that's pressed and released before the next tick still counts,
+/// once.
+///
+pub struct HeldKeys {
+    keys: PaddleKeys,
+    last_pressed: Option<Vertical>,
+    tapped: Option<Vertical>,
+}
+
+impl HeldKeys {
+    pub fn new(keys: PaddleKeys) -> HeldKeys {
+        HeldKeys {
+            keys,
+            last_pressed: None,
+            tapped: None,
+        }
+    }
+
+    /// Must be called for every key press event that isn't a repeat.
+    pub fn key_down(&mut self, keycode: Keycode) {
+        let dir = if self.keys.up.contains(&keycode) {
+            Vertical::Up
+        } else if self.keys.down.contains(&keycode) {
+            Vertical::Down
+        } else {
+            return;
+        };
+        self.last_pressed = Some(dir);
+        self.tapped = Some(dir);
+    }
+
+    /// The input for the next tick, given the keyboard's current state.
+    pub fn sample(&mut self, event_pump: &EventPump) -> UserInput {
+        let keyboard = event_pump.keyboard_state();
+        let is_held = |keycodes: &[Keycode]| {
+            keycodes.iter()
+                .filter_map(|k| Scancode::from_keycode(*k))
+                .any(|s| keyboard.is_scancode_pressed(s))
+        };
+        let up_held = is_held(&self.keys.up);
+        let down_held = is_held(&self.keys.down);
+        self.resolve(up_held, down_held)
+    }
+
+    fn resolve(&mut self, up_held: bool, down_held: bool) -> UserInput {
+        let tapped = self.tapped.take();
+        let dir = match (up_held, down_held) {
+            (true, false) => Some(Vertical::Up),
+            (false, true) => Some(Vertical::Down),
+            (true, true) => self.last_pressed,
+            (false, false) => tapped,
+        };
+        match dir {
+            Some(Vertical::Up) => UserInput::Up,
+            Some(Vertical::Down) => UserInput::Down,
+            None => UserInput::Nothing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_key_repeats_every_tick() {
+        let mut keys = HeldKeys::new(KeyBindings::default().paddle);
+        keys.key_down(Keycode::Up);
+        assert_eq!(keys.resolve(true, false), UserInput::Up);
+        assert_eq!(keys.resolve(true, false), UserInput::Up);
+        assert_eq!(keys.resolve(false, false), UserInput::Nothing);
+    }
+
+    #[test]
+    fn last_pressed_wins() {
+        let mut keys = HeldKeys::new(KeyBindings::default().paddle);
+        keys.key_down(Keycode::Up);
+        keys.key_down(Keycode::J);
+        assert_eq!(keys.resolve(true, true), UserInput::Down);
+        keys.key_down(Keycode::K);
+        assert_eq!(keys.resolve(true, true), UserInput::Up);
+        // releasing the last one goes back to the other.
+        assert_eq!(keys.resolve(false, true), UserInput::Down);
+    }
+
+    #[test]
+    fn tap_counts_once() {
+        let mut keys = HeldKeys::new(KeyBindings::default().paddle);
+        keys.key_down(Keycode::Down);
+        assert_eq!(keys.resolve(false, false), UserInput::Down);
+        assert_eq!(keys.resolve(false, false), UserInput::Nothing);
+    }
+
+    #[test]
+    fn actions_are_not_paddle_keys() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.action_for_key(Keycode::P), Some(UserInput::Pause));
+        assert_eq!(bindings.action_for_key(Keycode::Up), None);
+        assert_eq!(bindings.action_for_key(Keycode::J), None);
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(keycode_from_name("Up"), Some(Keycode::Up));
+        assert_eq!(keycode_from_name("enter"), Some(Keycode::Return));
+        assert_eq!(keycode_from_name("K"), Some(Keycode::K));
+        assert_eq!(keycode_from_name("k"), Some(Keycode::K));
+        assert_eq!(keycode_from_name("7"), Some(Keycode::Num7));
+        assert_eq!(keycode_from_name("/"), Some(Keycode::Slash));
+        assert_eq!(keycode_from_name("F12"), Some(Keycode::F12));
+        assert_eq!(keycode_from_name("KK"), None);
+        assert_eq!(keycode_from_name(""), None);
+        assert_eq!(keycode_name(Keycode::Escape), "Escape");
+        assert_eq!(keycode_name(Keycode::K), "K");
+        assert_eq!(keycode_name(Keycode::Comma), ",");
+        assert!(is_reserved(Keycode::F11));
+        assert!(is_reserved(Keycode::F5));
+        assert!(is_reserved(Keycode::F10));
+        assert!(!is_reserved(Keycode::F1));
+    }
+
+    #[test]
+    fn paddles_are_independent() {
+        let mut keys = HeldKeys::new(KeyBindings::default().second_paddle);
+        keys.key_down(Keycode::Up);
+        assert_eq!(keys.resolve(false, false), UserInput::Nothing);
+        keys.key_down(Keycode::W);
+        assert_eq!(keys.resolve(false, false), UserInput::Up);
+    }
+
+    #[test]
+    fn other_keys_are_ignored() {
+        let mut keys = HeldKeys::new(KeyBindings::default().paddle);
+        keys.key_down(Keycode::A);
+        assert_eq!(keys.resolve(false, false), UserInput::Nothing);
+    }
+}
@@ -4,8 +4,9 @@ use sdl2::{
 
 use std::{
     str,
+    fmt,
     process::{Command, Stdio, Child},
-    io::{Write, BufRead, BufReader},
+    io::{self, Write, BufRead, BufReader},
     fs::File,
     thread,
     time::Duration,
@@ -13,8 +14,8 @@ use std::{
 };
 
 use crate::{
-    parse_out,
-    game::{GameState, UserInput},
+    parse_out::{ParseError, OutputFormat},
+    game::{self, Difficulty, GameEvent, GameState, UserInput, Inputs},
 };
 
 // Name of the lambda calculus interpreter.
@@ -35,19 +36,135 @@ const GAME_OVER: &str = "gameOver";
 const UPDATE_STATE: &str = "nextState";
 const GET_RECTS: &str = "getScreenRects";
 
+// These symbols are optional, and are only used if the source file defines them.
+//
+const UPDATE_STATE_TWO_PLAYERS: &str = "nextStateTwoPlayers";
+// receives the difficulty, as a selector `\easy normal hard insane -> ...`,
+// and evaluates the initial state.
+const INITIAL_STATE_WITH_DIFFICULTY: &str = "initStateWithDifficulty";
+const WINDOW_TITLE: &str = "windowTitle";
+const GET_TEXT: &str = "getScreenText";
+// receives a state and evaluates a list of tags for what happened during the
+// update that led to it; see GameEvent::from_tag.
+const GET_EVENTS: &str = "getEvents";
+// receives a state and evaluates the left and right paddles' scores, as a
+// list of two numbers.
+const GET_SCORES: &str = "getScores";
+
+// Optional user inputs. When the source doesn't define one of these, pausing,
+// restarting and quitting are handled by us instead, and serving is ignored.
+//
+const USER_INPUT_PAUSE: &str = "pause";
+const USER_INPUT_SERVE: &str = "serve";
+const USER_INPUT_RESTART: &str = "restart";
+const USER_INPUT_QUIT: &str = "quit";
+const OPTIONAL_USER_INPUTS: &[&str] = &[
+    USER_INPUT_PAUSE,
+    USER_INPUT_SERVE,
+    USER_INPUT_RESTART,
+    USER_INPUT_QUIT,
+];
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the source file or talking to the interpreter process failed.
+    Io {
+        context: String,
+        error: io::Error,
+    },
+    /// The interpreter process doesn't have one of the streams we talk through.
+    Protocol(&'static str),
+    /// The source file doesn't define a symbol needed for what was asked.
+    MissingSymbol(&'static str),
+    /// The interpreter died, e.g. due to a syntax error in the source file,
+    /// while evaluating an expression that begins with `symbol`.
+    Terminated {
+        symbol: String,
+    },
+    /// The interpreter's output for an expression beginning with `symbol`
+    /// isn't in the encoding we expected.
+    Parse {
+        symbol: String,
+        error: ParseError,
+    },
+}
+
+impl Error {
+    fn io(context: &str, error: io::Error) -> Error {
+        Error::Io {
+            context: context.to_string(),
+            error,
+        }
+    }
+
+    fn parse(input: &str, error: ParseError) -> Error {
+        Error::Parse {
+            symbol: first_symbol(input),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { context, error } => write!(f, "{}: {}", context, error),
+            Error::Protocol(msg) => write!(f, "{}", msg),
+            Error::MissingSymbol(symbol) => {
+                write!(f, "the source file doesn't define `{}`", symbol)
+            },
+            Error::Terminated { symbol } => {
+                write!(f, "lambda interpreter terminated while evaluating `{}`", symbol)
+            },
+            Error::Parse { symbol, error } => {
+                write!(f, "malformed output for `{}`: {}", symbol, error)
+            },
+        }
+    }
+}
+
+// The expressions we evaluate may contain a whole game state, so errors only
+// keep the symbol being applied.
+//
+fn first_symbol(input: &str) -> String {
+    input.split_whitespace().next().unwrap_or("").to_string()
+}
+
 pub struct State {
     lambda_proc: Child,
     scaling_factor: i32,
     x_offset: i32,
     y_offset: i32,
+    window_title: Option<String>,
+    has_text: bool,
+    has_events: bool,
+    has_scores: bool,
+    // which of the optional user inputs are defined.
+    defined_inputs: Vec<&'static str>,
+    two_players: bool,
+    format: OutputFormat,
+    init_state: String,
     state: String,
+    // only used when the source doesn't define the respective input.
+    paused: bool,
+    quit_requested: bool,
+    // what happened during the last update.
+    events: Vec<GameEvent>,
 }
 
 impl State {
-    pub fn new(filename: &str) -> Result<State, String> {
+    /// If format is None, it's detected from the interpreter's first output.
+    /// Two-player mode requires the source to define a two-player variant
+    /// of the function that computes the next state, and any difficulty but
+    /// normal a variant of the initial state that takes the difficulty.
+    ///
+    pub fn new(filename: &str,
+               format: Option<OutputFormat>,
+               two_players: bool,
+               difficulty: Difficulty) -> Result<State, Error> {
         let file = match File::open(filename) {
             Ok(f) => f,
-            Err(e) => return Err(format!("failed to open file '{}': '{}'", filename, e)),
+            Err(e) => return Err(Error::io(&format!("failed to open file '{}'", filename), e)),
         };
 
         let lambda_proc = Command::new(LAMBDA_CALC_BIN_NAME)
@@ -57,12 +174,12 @@ impl State {
             .spawn();
         let mut lambda_proc = match lambda_proc {
             Ok(p) => p,
-            Err(e) => return Err(format!("failed to spawn lambda interpreter process: '{}'.
-Make sure the 'lambda_calc' binary is installed in a directory included in your PATH.", e)),
+            Err(e) => return Err(Error::io("failed to spawn lambda interpreter process.
+Make sure the 'lambda_calc' binary is installed in a directory included in your PATH", e)),
         };
 
         let in_stream_unwrapped = match lambda_proc.stdin {
-            None => return Err(format!("no input stream in lambda interpreter process")),
+            None => return Err(Error::Protocol("no input stream in lambda interpreter process")),
             Some(ref mut stream) => stream,
         };
 
@@ -70,93 +187,292 @@ Make sure the 'lambda_calc' binary is installed in a directory included in your
         for line in reader.lines() {
             let line = match line {
                 Ok(s) => s,
-                Err(e) => return Err(format!("failed to read line from file: {}", e)),
+                Err(e) => return Err(Error::io("failed to read line from file", e)),
             };
-            if let Err(e) = write!(in_stream_unwrapped, "{}\n", &line) {
-                return Err(format!("failed to write to process's input stream: '{}'", e));
+            if let Err(e) = writeln!(in_stream_unwrapped, "{}", &line) {
+                return Err(Error::io("failed to write to process's input stream", e));
             };
         }
         let scaling_factor = get_child_output_line_for_input(&mut lambda_proc,
-                                                             &SCALING_FACTOR_NAME)?;
-        let scaling_factor = parse_out::clni_to_int(&scaling_factor)?;
+                                                             SCALING_FACTOR_NAME)?;
+        let format = format.unwrap_or_else(|| OutputFormat::detect(&scaling_factor));
+        let scaling_factor = format.clni_to_int(&scaling_factor)
+            .map_err(|e| Error::parse(SCALING_FACTOR_NAME, e))?;
 
         let x_offset = get_child_output_line_for_input(&mut lambda_proc,
-                                                            &X_OFFSET_NAME)?;
-        let x_offset = parse_out::clni_to_int(&x_offset)?;
+                                                            X_OFFSET_NAME)?;
+        let x_offset = format.clni_to_int(&x_offset)
+            .map_err(|e| Error::parse(X_OFFSET_NAME, e))?;
 
         let y_offset = get_child_output_line_for_input(&mut lambda_proc,
-                                                            &Y_OFFSET_NAME)?;
-        let y_offset = parse_out::clni_to_int(&y_offset)?;
+                                                            Y_OFFSET_NAME)?;
+        let y_offset = format.clni_to_int(&y_offset)
+            .map_err(|e| Error::parse(Y_OFFSET_NAME, e))?;
+
+        let window_title = match get_optional_output(&mut lambda_proc, WINDOW_TITLE)? {
+            Some(title) => Some(format.parse_string(&title)
+                                .map_err(|e| Error::parse(WINDOW_TITLE, e))?),
+            None => None,
+        };
+        let has_text = get_optional_output(&mut lambda_proc, GET_TEXT)?.is_some();
+        let has_events = get_optional_output(&mut lambda_proc, GET_EVENTS)?.is_some();
+        let has_scores = get_optional_output(&mut lambda_proc, GET_SCORES)?.is_some();
 
-        let init_state = get_child_output_line_for_input(&mut lambda_proc,
-                                                         &INITIAL_STATE)?;
+        let mut defined_inputs = Vec::new();
+        for input in OPTIONAL_USER_INPUTS {
+            if get_optional_output(&mut lambda_proc, input)?.is_some() {
+                defined_inputs.push(*input);
+            }
+        }
+        if two_players && get_optional_output(&mut lambda_proc, UPDATE_STATE_TWO_PLAYERS)?.is_none() {
+            return Err(Error::MissingSymbol(UPDATE_STATE_TWO_PLAYERS));
+        }
+
+        let has_difficulty = get_optional_output(&mut lambda_proc,
+                                                 INITIAL_STATE_WITH_DIFFICULTY)?.is_some();
+        let init_state = if has_difficulty {
+            let lambda_expr = format!("{} {}",
+                                      INITIAL_STATE_WITH_DIFFICULTY,
+                                      difficulty_selector(difficulty));
+            get_child_output_line_for_input(&mut lambda_proc, &lambda_expr)?
+        } else if difficulty != Difficulty::Normal {
+            return Err(Error::MissingSymbol(INITIAL_STATE_WITH_DIFFICULTY));
+        } else {
+            get_child_output_line_for_input(&mut lambda_proc, INITIAL_STATE)?
+        };
         Ok(State {
             lambda_proc,
             scaling_factor,
             x_offset,
             y_offset,
-            state: init_state,
+            window_title,
+            has_text,
+            has_events,
+            has_scores,
+            defined_inputs,
+            two_players,
+            format,
+            state: init_state.clone(),
+            init_state,
+            paused: false,
+            quit_requested: false,
+            events: Vec::new(),
         })
     }
 
-    fn get_output(&mut self, input: &str) -> String {
-        let output = match get_child_output_line_for_input(&mut self.lambda_proc,
-                                                           input) {
-            Ok(s) => s,
-            Err(e) => panic!("failed to get lambda interpreter output: '{}'", e),
-        };
-        output
+    pub fn window_title(&self) -> Option<&str> {
+        self.window_title.as_deref()
+    }
+
+    fn get_output(&mut self, input: &str) -> Result<String, Error> {
+        get_child_output_line_for_input(&mut self.lambda_proc, input)
+    }
+
+    // Anything but two scores that aren't negative is ignored, like
+    // unknown event tags.
+    fn get_scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        if !self.has_scores {
+            return Ok(None);
+        }
+        let lambda_expr = format!("{} {}", GET_SCORES, &self.state);
+        let scores_str = self.get_output(&lambda_expr)?;
+        let scores = self.format.parse_int_list(&scores_str)
+            .map_err(|e| Error::parse(&lambda_expr, e))?;
+        match scores[..] {
+            [left, right] if left >= 0 && right >= 0 => Ok(Some((left as usize, right as usize))),
+            _ => Ok(None),
+        }
     }
 }
 
 impl GameState for State {
-    fn game_over(&mut self) -> bool {
+    fn game_over(&mut self) -> Result<bool, game::Error> {
+        if self.quit_requested {
+            return Ok(true);
+        }
         let lambda_expr = format!("{} {}", GAME_OVER, &self.state);
-        let answer_str = self.get_output(&lambda_expr);
+        let answer_str = self.get_output(&lambda_expr)?;
 
-        let answer = match parse_out::parse_church_bool(&answer_str) {
-            Ok(ans) => ans,
-            Err(e) => panic!("failed to parse output as a Church boolean: '{}'", e),
-        };
-        answer
+        let answer = self.format.parse_church_bool(&answer_str)
+            .map_err(|e| Error::parse(&lambda_expr, e))?;
+        Ok(answer)
     }
 
-    fn update(&mut self, input: UserInput) {
+    fn update(&mut self, inputs: Inputs) -> Result<(), game::Error> {
+        self.events.clear();
+        let input = inputs.player;
         let user_input = match input {
             UserInput::Up => USER_INPUT_UP,
             UserInput::Down => USER_INPUT_DOWN,
             UserInput::Nothing => USER_INPUT_NONE,
+            UserInput::Pause => USER_INPUT_PAUSE,
+            UserInput::Serve => USER_INPUT_SERVE,
+            UserInput::Restart => USER_INPUT_RESTART,
+            UserInput::Quit => USER_INPUT_QUIT,
+        };
+        let is_defined = !OPTIONAL_USER_INPUTS.contains(&user_input)
+            || self.defined_inputs.contains(&user_input);
+        let user_input = if is_defined {
+            user_input
+        } else {
+            // the source can't handle this input, so we do it ourselves.
+            match input {
+                UserInput::Pause => {
+                    self.paused = !self.paused;
+                    return Ok(());
+                },
+                UserInput::Restart => {
+                    self.state = self.init_state.clone();
+                    self.paused = false;
+                    return Ok(());
+                },
+                UserInput::Quit => {
+                    self.quit_requested = true;
+                    return Ok(());
+                },
+                _ => USER_INPUT_NONE,
+            }
         };
-        let lambda_expr = format!("{} {} {}", UPDATE_STATE, &self.state, user_input);
-        self.state = self.get_output(&lambda_expr);
+        if self.paused {
+            return Ok(());
+        }
+        let lambda_expr = if self.two_players {
+            // the second player can only move.
+            let second_input = match inputs.second_player {
+                UserInput::Up => USER_INPUT_UP,
+                UserInput::Down => USER_INPUT_DOWN,
+                _ => USER_INPUT_NONE,
+            };
+            format!("{} {} {} {}", UPDATE_STATE_TWO_PLAYERS, &self.state, user_input, second_input)
+        } else {
+            format!("{} {} {}", UPDATE_STATE, &self.state, user_input)
+        };
+        self.state = self.get_output(&lambda_expr)?;
+
+        if self.has_events {
+            let lambda_expr = format!("{} {}", GET_EVENTS, &self.state);
+            let events_str = self.get_output(&lambda_expr)?;
+            let tags = self.format.parse_int_list(&events_str)
+                .map_err(|e| Error::parse(&lambda_expr, e))?;
+            // unknown tags are ignored, so that sources can report more
+            // than we make sounds for.
+            self.events = tags.into_iter().filter_map(GameEvent::from_tag).collect();
+        }
+        Ok(())
     }
 
-    fn get_rects(&mut self) -> Vec<Rect> {
+    fn get_rects(&mut self) -> Result<Vec<Rect>, game::Error> {
         let lambda_expr = format!("{} {}", GET_RECTS, &self.state);
-        let rects_str = self.get_output(&lambda_expr);
-
-        let rects = parse_out::parse_rect_list(&rects_str,
-                                               self.scaling_factor,
-                                               self.x_offset,
-                                               self.y_offset);
-        let rects = match rects {
-            Ok(r) => r,
-            Err(e) => panic!("failed to parse list of rectangles: '{}'", e),
-        };
-        rects
+        let rects_str = self.get_output(&lambda_expr)?;
+
+        let rects = self.format.parse_rect_list(&rects_str,
+                                                self.scaling_factor,
+                                                self.x_offset,
+                                                self.y_offset)
+            .map_err(|e| Error::parse(&lambda_expr, e))?;
+        Ok(rects)
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, game::Error> {
+        if self.paused {
+            return Ok(Some("PAUSED".to_string()));
+        }
+        if !self.has_text {
+            return Ok(None);
+        }
+        let lambda_expr = format!("{} {}", GET_TEXT, &self.state);
+        let text_str = self.get_output(&lambda_expr)?;
+
+        let text = self.format.parse_string(&text_str)
+            .map_err(|e| Error::parse(&lambda_expr, e))?;
+        if text.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(text))
+        }
+    }
+
+    // without the scores, the source can only tell who won with its text.
+    fn result(&mut self) -> Result<Option<String>, game::Error> {
+        match self.get_scores()? {
+            Some((left, right)) => Ok(Some(game::result_text(left, right, self.two_players))),
+            None => self.get_text(),
+        }
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, game::Error> {
+        Ok(self.get_scores()?)
+    }
+
+    fn dump_state(&mut self) -> Option<String> {
+        Some(format!("{}\n", self.state))
+    }
+
+    // the state term, as the interpreter printed it.
+    fn save_state(&mut self) -> Option<String> {
+        Some(format!("{}\n", self.state))
+    }
+
+    fn load_state(&mut self, saved: &str) -> Result<(), String> {
+        // the term is sent to the interpreter as a line of its own.
+        let saved = saved.trim();
+        if saved.is_empty() {
+            return Err("the state is empty".to_string());
+        }
+        if saved.contains('\n') {
+            return Err("the state isn't a single line".to_string());
+        }
+        self.state = saved.to_string();
+        self.paused = false;
+        self.quit_requested = false;
+        self.events.clear();
+        Ok(())
+    }
+}
+
+// Don't leave the interpreter running, however the game ends.
+impl Drop for State {
+    fn drop(&mut self) {
+        let _ = self.lambda_proc.kill();
+        let _ = self.lambda_proc.wait();
+    }
+}
+
+// The difficulty as a lambda term that selects one of four arguments, in
+// the order easy, normal, hard and insane.
+//
+fn difficulty_selector(difficulty: Difficulty) -> String {
+    format!("(\\easy normal hard insane -> {})", difficulty.name())
+}
+
+// Evaluate a symbol that the source file may or may not define.
+// An undefined symbol is just a free variable, which the interpreter
+// prints back unchanged, so that's how we tell it apart.
+//
+fn get_optional_output(child: &mut Child,
+                       symbol: &str) -> Result<Option<String>, Error> {
+    let output = get_child_output_line_for_input(child, symbol)?;
+    if output.trim() == symbol {
+        Ok(None)
+    } else {
+        Ok(Some(output))
     }
 }
 
 fn get_child_output_line_for_input(child: &mut Child,
-                                   input: &str) -> Result<String, String> {
+                                   input: &str) -> Result<String, Error> {
     let read_interval = Duration::from_millis(1);
 
     let child_stdin = match child.stdin {
         Some(ref mut stream) => stream,
-        None => return Err(format!("no stdin stream in lambda interpreter")),
+        None => return Err(Error::Protocol("no stdin stream in lambda interpreter")),
     };
-    if let Err(e) = write!(child_stdin, "{}\n", input) {
-        return Err(format!("failed to write to process's input stream: '{}'", e));
+    if let Err(e) = writeln!(child_stdin, "{}", input) {
+        return Err(Error::io("failed to write to process's input stream", e));
     }
 
     let mut output = String::new();
@@ -165,26 +481,26 @@ fn get_child_output_line_for_input(child: &mut Child,
         {
             let child_stdout = match child.stdout {
                 Some(ref mut stream) => stream,
-                None => return Err(format!("no stdout stream in lambda interpreter")),
+                None => return Err(Error::Protocol("no stdout stream in lambda interpreter")),
             };
             let mut output_reader = BufReader::new(child_stdout);
             read_line_retval = output_reader.read_line(&mut output);
         }
         match read_line_retval {
-            Err(e) => return Err(e.to_string()),
+            Err(e) => return Err(Error::io("failed to read from process's output stream", e)),
             Ok(0) => {
                 // If we're here, it's possible that:
                 // - the output is just not availibale yet, or
                 // - there was a syntax error that made the child process die.
                 //
                 match child.try_wait() {
-                    Err(e) => return Err(format!("failed to check if lambda interpreter terminated: '{}'", e)),
-                    Ok(Some(_)) => return Err(format!("lambda interpreter already terminated; input was `{}`", input)),
+                    Err(e) => return Err(Error::io("failed to check if lambda interpreter terminated", e)),
+                    Ok(Some(_)) => return Err(Error::Terminated { symbol: first_symbol(input) }),
                     Ok(None) => thread::sleep(read_interval), // wait before trying to read again
                 };
             },
             Ok(_) => break,
         };
     };
-    return Ok(output.replace("\n", ""));
+    Ok(output.replace("\n", ""))
 }
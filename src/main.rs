@@ -2,75 +2,966 @@ mod pong;
 mod lambda;
 mod game;
 mod parse_out;
+mod font;
+mod timing;
+mod input;
+mod controller;
+mod headless;
+mod replay;
+mod raster;
+mod terminal;
+mod window;
+mod stats;
+mod debug;
+mod sound;
+mod config;
+mod net;
+mod stream;
+mod snapshot;
 
 use std::{
     env,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
 };
 
+use config::Config;
+use debug::Debugger;
+use game::{Difficulty, GameState, Pace};
+use net::{Hello, NetGame, Role};
+use parse_out::OutputFormat;
+use raster::{FrameExport, ImageFormat};
+use replay::{Recording, Replay, Replaying, Source};
+use sound::{Speaker, WavWriter};
+use stats::Stats;
+use snapshot::{Snapshot, SnapshotFile};
+use stream::{Address, Publisher, Spectating};
+use timing::{Timing, Lag};
+
+// The exit codes, by what went wrong.
+const EXIT_USAGE:u8 = 1;
+// a file, the window or the terminal couldn't be set up.
+const EXIT_SETUP:u8 = 2;
+// the lambda backend's source or interpreter couldn't be read, run or talked
+// to, or the interpreter died.
+const EXIT_INTERPRETER:u8 = 3;
+// the source doesn't define a symbol, or evaluates to something unexpected.
+const EXIT_SOURCE:u8 = 4;
+// a frame, or anything else being written out, couldn't be.
+const EXIT_OUTPUT:u8 = 5;
+// the other player, or the game to spectate, couldn't be reached, or the
+// games went out of sync.
+const EXIT_NETWORK:u8 = 6;
+
+const DEFAULT_SNAPSHOT:&str = "snapshot.txt";
+
+fn lambda_exit_code(error: &lambda::Error) -> u8 {
+    match error {
+        lambda::Error::Io { .. }
+        | lambda::Error::Protocol(_)
+        | lambda::Error::Terminated { .. } => EXIT_INTERPRETER,
+        lambda::Error::MissingSymbol(_) | lambda::Error::Parse { .. } => EXIT_SOURCE,
+    }
+}
+
+fn exit_code(error: &game::Error) -> u8 {
+    match error {
+        game::Error::Backend(e) => lambda_exit_code(e),
+        game::Error::Render(_) => EXIT_OUTPUT,
+        game::Error::Network(_) => EXIT_NETWORK,
+    }
+}
+
 enum Backend {
     Native,
     Lambda(String),
+    // watch a game published elsewhere.
+    Spectate(Address),
+}
+
+// How to reach the other player in a network game.
+enum Peer {
+    // wait for them on this local address and port.
+    Host(String, u16),
+    // connect to them at this address.
+    Guest(String),
+}
+
+struct Options {
+    backend: Backend,
+    // None means it's detected automatically.
+    output_format: Option<OutputFormat>,
+    timing: Timing,
+    two_players: bool,
+    difficulty: Difficulty,
+    display: window::Display,
+    // play in the terminal rather than in a window.
+    terminal: bool,
+    // how many ticks to run without a window, if any.
+    headless: Option<u64>,
+    script: Option<String>,
+    // where to save frames in headless mode, if anywhere.
+    frames: Option<FrameExport>,
+    // the slow motion factor, if the debug mode is on.
+    debug: Option<u32>,
+    // play sound effects, and save them to a WAV file if given.
+    sound: bool,
+    wav: Option<String>,
+    // show frame timings on screen.
+    overlay: bool,
+    // where to save the frame timings at exit, if anywhere.
+    stats: Option<String>,
+    // replay files to write to and to read the inputs from.
+    record: Option<String>,
+    replay: Option<String>,
+    // key bindings, colors and the native backend's parameters.
+    config: Config,
+    // the other player, in a network game, and how many ticks ahead inputs
+    // are sent to them.
+    peer: Option<Peer>,
+    input_delay: u32,
+    // where to publish the frames for spectators, if anywhere.
+    publish: Option<Address>,
+    // where F9 saves the game and F10 loads it from, and the snapshot to
+    // start from, if any.
+    snapshot: PathBuf,
+    resume: Option<String>,
+    // the snapshot file, once the game it's for is known.
+    snapshots: Option<SnapshotFile>,
 }
 
 fn usage() {
-    eprintln!("usage: <program_name> <backend>");
+    eprintln!("usage: <program_name> <backend> [options]");
     eprintln!("where <backend> is one of:");
     eprintln!("\t-n\tnative Rust backend");
     eprintln!("\t-l <filename>\tlambda calculus backend using source <filename>");
+    eprintln!("\t--spectate <address>\twatch the game published at <address>, either host:port");
+    eprintln!("\t\t\tor unix:<path>, instead of playing.");
+    eprintln!("and [options] are any of:");
+    eprintln!("\t-f <format>\thow the interpreter prints terms; either 'named' or 'de-bruijn'.");
+    eprintln!("\t\t\tDetected automatically if not given.");
+    eprintln!("\t-r <rate>\tsimulation ticks per second (default: {}).", timing::DEFAULT_TICK_RATE);
+    eprintln!("\t--no-vsync\tdon't wait for vsync; frames are limited to the tick rate instead.");
+    eprintln!("\t--catch-up\twhen the backend is too slow, skip frames to keep the game's");
+    eprintln!("\t\t\tspeed, rather than playing in slow motion.");
+    eprintln!("\t-2\t\ttwo-player mode: W and S move the left paddle instead of the CPU.");
+    eprintln!("\t--difficulty <level>\thow well the CPU plays: 'easy', 'normal' (default), 'hard'");
+    eprintln!("\t\t\tor 'insane'.");
+    eprintln!("\t--fullscreen\tstart in fullscreen; F11 toggles it at any time.");
+    eprintln!("\t--integer-scale\tonly scale the game by whole numbers when the window is resized.");
+    eprintln!("\t--terminal\tplay in the terminal instead of a window.");
+    eprintln!("\t--headless <ticks>\trun for <ticks> ticks without a window, printing each");
+    eprintln!("\t\t\ttick's rectangles to stdout as a line of JSON.");
+    eprintln!("\t--script <filename>\tin headless mode, read the inputs from <filename>.");
+    eprintln!("\t--frames <dir>\tin headless mode, save frames as images in <dir>.");
+    eprintln!("\t--frame-every <n>\tsave every <n>th tick's frame (default: 1).");
+    eprintln!("\t--frame-format <format>\teither 'png' (default) or 'ppm'.");
+    eprintln!("\t--debug\t\tdebug mode: F5 pauses, F6 runs a single tick, F7 toggles slow motion");
+    eprintln!("\t\t\tand F8 dumps the state to a file.");
+    eprintln!("\t--slow-motion <n>\tin debug mode, slow motion runs one of every <n> ticks");
+    eprintln!("\t\t\t(default: {}).", debug::DEFAULT_SLOW_MOTION);
+    eprintln!("\t--mute\t\tdon't play sound effects.");
+    eprintln!("\t--wav <filename>\tsave the sound effects to <filename>, following the game's ticks.");
+    eprintln!("\t--overlay\tshow the frame rate and frame times on screen.");
+    eprintln!("\t--stats <filename>\ttime every frame, saving the timings to <filename> as CSV");
+    eprintln!("\t\t\tand printing a summary at exit.");
+    eprintln!("\t--record <filename>\trecord every tick's inputs to the replay <filename>.");
+    eprintln!("\t--replay <filename>\tplay back the inputs recorded in <filename>.");
+    eprintln!("\t--speed <factor>\tplay the replay <factor> times as fast (e.g. 0.5 or 4).");
+    eprintln!("\t--config <filename>\tread key bindings, colors, the window size and the native");
+    eprintln!("\t\t\tbackend's parameters from the TOML file <filename>.");
+    eprintln!("\t--set <section.key=value>\tset a key of the config, overriding the file.");
+    eprintln!("\t--host <port>\tplay over the network: wait for the other player on <port>,");
+    eprintln!("\t\t\tand play the right paddle.");
+    eprintln!("\t--bind <address>\twith '--host', only accept the other player on the local");
+    eprintln!("\t\t\t<address>, e.g. 127.0.0.1 (default: {}, every interface).", net::DEFAULT_BIND);
+    eprintln!("\t--connect <address:port>\tplay over the network against the player hosting");
+    eprintln!("\t\t\tat <address:port>, with the left paddle.");
+    eprintln!("\t--input-delay <ticks>\tin a network game, how many ticks inputs take effect");
+    eprintln!("\t\t\tafter (default: {}); more hides more latency.", net::DEFAULT_INPUT_DELAY);
+    eprintln!("\t--snapshot <filename>\tsave the game to <filename> with F9, and load it with F10");
+    eprintln!("\t\t\t(default: the file given with '--resume', or {}).", DEFAULT_SNAPSHOT);
+    eprintln!("\t--resume <filename>\tstart from the game saved in <filename>.");
+    eprintln!("\t--publish <address>\tsend every frame to spectators connecting to <address>,");
+    eprintln!("\t\t\teither host:port or unix:<path>.");
 }
 
-fn parse_args() -> Option<Backend> {
+fn parse_args() -> Option<Options> {
     let mut args = env::args();
     args.next(); // skip program name
-    let backend = match args.next() {
+
+    let mut backend = None;
+    let mut output_format = None;
+    let mut timing = Timing::default();
+    let mut two_players = false;
+    let mut difficulty = Difficulty::default();
+    let mut display = window::Display::default();
+    let mut terminal = false;
+    let mut headless = None;
+    let mut script = None;
+    let mut debug = false;
+    let mut slow_motion = None;
+    let mut sound = true;
+    let mut wav = None;
+    let mut overlay = false;
+    let mut stats = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut speed = None;
+    let mut frames_dir = None;
+    let mut frame_every = 1;
+    let mut frame_format = ImageFormat::Png;
+    let mut config_file = None;
+    let mut config_overrides = Vec::new();
+    let mut peer = None;
+    let mut bind = None;
+    let mut input_delay = None;
+    let mut publish = None;
+    let mut snapshot = None;
+    let mut resume = None;
+    while let Some(arg) = args.next() {
+        if arg == "-n" {
+            backend = Some(Backend::Native);
+        } else if arg == "-l" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '-l' requires a filename.");
+                    usage();
+                    return None;
+                },
+                Some(filename) => {
+                    backend = Some(Backend::Lambda(filename));
+                },
+            }
+        } else if arg == "--spectate" || arg == "--publish" {
+            match args.next().map(|address| Address::parse(&address)) {
+                None => {
+                    eprintln!("error: option '{}' requires an address.", arg);
+                    usage();
+                    return None;
+                },
+                Some(Err(e)) => {
+                    eprintln!("error: option '{}': {}.", arg, e);
+                    usage();
+                    return None;
+                },
+                Some(Ok(address)) if arg == "--spectate" => backend = Some(Backend::Spectate(address)),
+                Some(Ok(address)) => publish = Some(address),
+            }
+        } else if arg == "-f" {
+            match args.next().as_deref().map(OutputFormat::from_name) {
+                Some(Some(format)) => output_format = Some(format),
+                _ => {
+                    eprintln!("error: option '-f' requires either 'named' or 'de-bruijn'.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "-r" {
+            match args.next().map(|rate| rate.parse::<u32>()) {
+                Some(Ok(rate)) if rate > 0 => timing.tick_rate = rate,
+                _ => {
+                    eprintln!("error: option '-r' requires a positive integer.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--no-vsync" {
+            timing.vsync = false;
+        } else if arg == "--catch-up" {
+            timing.lag = Lag::CatchUp;
+        } else if arg == "-2" {
+            two_players = true;
+        } else if arg == "--difficulty" {
+            match args.next().as_deref().map(Difficulty::from_name) {
+                Some(Some(level)) => difficulty = level,
+                _ => {
+                    eprintln!("error: option '--difficulty' requires 'easy', 'normal', 'hard' or 'insane'.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--terminal" {
+            terminal = true;
+        } else if arg == "--headless" {
+            match args.next().map(|ticks| ticks.parse::<u64>()) {
+                Some(Ok(ticks)) => headless = Some(ticks),
+                _ => {
+                    eprintln!("error: option '--headless' requires a number of ticks.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--script" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--script' requires a filename.");
+                    usage();
+                    return None;
+                },
+                Some(filename) => script = Some(filename),
+            }
+        } else if arg == "--frames" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--frames' requires a directory.");
+                    usage();
+                    return None;
+                },
+                Some(dir) => frames_dir = Some(PathBuf::from(dir)),
+            }
+        } else if arg == "--frame-every" {
+            match args.next().map(|n| n.parse::<u64>()) {
+                Some(Ok(n)) if n > 0 => frame_every = n,
+                _ => {
+                    eprintln!("error: option '--frame-every' requires a positive integer.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--frame-format" {
+            match args.next().as_deref().map(ImageFormat::from_name) {
+                Some(Some(format)) => frame_format = format,
+                _ => {
+                    eprintln!("error: option '--frame-format' requires either 'png' or 'ppm'.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--debug" {
+            debug = true;
+        } else if arg == "--slow-motion" {
+            match args.next().map(|n| n.parse::<u32>()) {
+                Some(Ok(n)) if n > 0 => slow_motion = Some(n),
+                _ => {
+                    eprintln!("error: option '--slow-motion' requires a positive integer.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--fullscreen" {
+            display.fullscreen = true;
+        } else if arg == "--integer-scale" {
+            display.integer_scale = true;
+        } else if arg == "--mute" {
+            sound = false;
+        } else if arg == "--wav" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--wav' requires a filename.");
+                    usage();
+                    return None;
+                },
+                Some(filename) => wav = Some(filename),
+            }
+        } else if arg == "--overlay" {
+            overlay = true;
+        } else if arg == "--stats" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--stats' requires a filename.");
+                    usage();
+                    return None;
+                },
+                Some(filename) => stats = Some(filename),
+            }
+        } else if arg == "--record" || arg == "--replay" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '{}' requires a filename.", arg);
+                    usage();
+                    return None;
+                },
+                Some(filename) if arg == "--record" => record = Some(filename),
+                Some(filename) => replay = Some(filename),
+            }
+        } else if arg == "--speed" {
+            match args.next().map(|factor| factor.parse::<f64>()) {
+                Some(Ok(factor)) if factor > 0.0 && factor.is_finite() => speed = Some(factor),
+                _ => {
+                    eprintln!("error: option '--speed' requires a positive number.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--config" || arg == "--set" {
+            match args.next() {
+                None if arg == "--config" => {
+                    eprintln!("error: option '--config' requires a filename.");
+                    usage();
+                    return None;
+                },
+                None => {
+                    eprintln!("error: option '--set' requires 'section.key=value'.");
+                    usage();
+                    return None;
+                },
+                Some(filename) if arg == "--config" => config_file = Some(filename),
+                Some(assignment) => config_overrides.push(assignment),
+            }
+        } else if arg == "--snapshot" || arg == "--resume" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '{}' requires a filename.", arg);
+                    usage();
+                    return None;
+                },
+                Some(filename) if arg == "--snapshot" => snapshot = Some(PathBuf::from(filename)),
+                Some(filename) => resume = Some(filename),
+            }
+        } else if arg == "--host" {
+            match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => peer = Some(Peer::Host(net::DEFAULT_BIND.to_string(), port)),
+                _ => {
+                    eprintln!("error: option '--host' requires a port.");
+                    usage();
+                    return None;
+                },
+            }
+        } else if arg == "--connect" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--connect' requires an address and port.");
+                    usage();
+                    return None;
+                },
+                Some(address) => peer = Some(Peer::Guest(address)),
+            }
+        } else if arg == "--bind" {
+            match args.next() {
+                None => {
+                    eprintln!("error: option '--bind' requires an address.");
+                    usage();
+                    return None;
+                },
+                Some(address) => bind = Some(address),
+            }
+        } else if arg == "--input-delay" {
+            match args.next().map(|ticks| ticks.parse::<u32>()) {
+                Some(Ok(ticks)) => input_delay = Some(ticks),
+                _ => {
+                    eprintln!("error: option '--input-delay' requires a number of ticks.");
+                    usage();
+                    return None;
+                },
+            }
+        } else {
+            eprintln!("unknown option '{}'", arg);
+            usage();
+            return None;
+        }
+    }
+
+    if script.is_some() && headless.is_none() {
+        eprintln!("error: option '--script' requires '--headless'.");
+        usage();
+        return None;
+    }
+    if slow_motion.is_some() && !debug {
+        eprintln!("error: option '--slow-motion' requires '--debug'.");
+        usage();
+        return None;
+    }
+    if debug && headless.is_some() {
+        eprintln!("error: options '--debug' and '--headless' can't be used together.");
+        usage();
+        return None;
+    }
+    if terminal && headless.is_some() {
+        eprintln!("error: options '--terminal' and '--headless' can't be used together.");
+        usage();
+        return None;
+    }
+    if frames_dir.is_some() && headless.is_none() {
+        eprintln!("error: option '--frames' requires '--headless'.");
+        usage();
+        return None;
+    }
+    if script.is_some() && replay.is_some() {
+        eprintln!("error: options '--script' and '--replay' can't be used together.");
+        usage();
+        return None;
+    }
+    if peer.is_some() && replay.is_some() {
+        eprintln!("error: a replay can't be played over the network.");
+        usage();
+        return None;
+    }
+    if resume.is_some() {
+        // replays and the other player's game always start from the
+        // beginning.
+        let conflict = if replay.is_some() {
+            Some("--replay")
+        } else if record.is_some() {
+            Some("--record")
+        } else if peer.is_some() {
+            Some("--host' or '--connect")
+        } else {
+            None
+        };
+        if let Some(option) = conflict {
+            eprintln!("error: options '--resume' and '{}' can't be used together.", option);
+            usage();
+            return None;
+        }
+    }
+    if let Some(address) = bind {
+        match peer {
+            Some(Peer::Host(ref mut bind, _)) => *bind = address,
+            _ => {
+                eprintln!("error: option '--bind' requires '--host'.");
+                usage();
+                return None;
+            },
+        }
+    }
+    if input_delay.is_some() && peer.is_none() {
+        eprintln!("error: option '--input-delay' requires '--host' or '--connect'.");
+        usage();
+        return None;
+    }
+    if let Some(Backend::Spectate(_)) = backend {
+        let playing = [
+            ("--headless", headless.is_some()),
+            ("--record", record.is_some()),
+            ("--replay", replay.is_some()),
+            ("--host", matches!(peer, Some(Peer::Host(..)))),
+            ("--connect", matches!(peer, Some(Peer::Guest(_)))),
+            ("--resume", resume.is_some()),
+        ];
+        if let Some((option, _)) = playing.iter().find(|(_, given)| *given) {
+            eprintln!("error: option '{}' can't be used when spectating.", option);
+            usage();
+            return None;
+        }
+    }
+    if let Some(factor) = speed {
+        if replay.is_none() {
+            eprintln!("error: option '--speed' requires '--replay'.");
+            usage();
+            return None;
+        }
+        timing.tick_rate = ((timing.tick_rate as f64 * factor).round() as u32).max(1);
+    }
+
+    let config = match Config::load(config_file.as_deref(), &config_overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return None;
+        },
+    };
+
+    match backend {
         None => {
             eprintln!("error: no backend specified.");
             usage();
             None
         },
-        Some(arg) => {
-            if arg == "-n" {
-                Some(Backend::Native)
-            } else if arg == "-l" {
-                match args.next() {
-                    None => {
-                        eprintln!("error: option '-l' requires a filename.");
-                        usage();
-                        None
-                    },
-                    Some(filename) => {
-                        Some(Backend::Lambda(filename))
-                    },
-                }
+        Some(backend) => Some(Options {
+            backend,
+            output_format,
+            timing,
+            two_players,
+            difficulty,
+            display,
+            terminal,
+            headless,
+            script,
+            frames: frames_dir.map(|dir| FrameExport::new(dir, frame_every, frame_format, config.colors)),
+            debug: if debug {
+                Some(slow_motion.unwrap_or(debug::DEFAULT_SLOW_MOTION))
             } else {
-                eprintln!("unknown option '{}'", arg);
-                usage();
                 None
+            },
+            sound,
+            wav,
+            overlay,
+            stats,
+            record,
+            replay,
+            config,
+            peer,
+            input_delay: input_delay.unwrap_or(net::DEFAULT_INPUT_DELAY),
+            publish,
+            snapshot: snapshot
+                .or_else(|| resume.as_ref().map(PathBuf::from))
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_SNAPSHOT)),
+            resume,
+            snapshots: None,
+        }),
+    }
+}
+
+// Where frames go besides the screen: the sound's WAV file and the
+// spectators, if any.
+type Outputs = (Option<WavWriter>, Option<Publisher>);
+
+fn run_headless<S: GameState>(state: &mut S,
+                              ticks: u64,
+                              mut script: headless::Script,
+                              frames: Option<FrameExport>,
+                              outputs: Outputs,
+                              stats: Option<&mut Stats>) -> Result<(), u8>
+{
+    if let Some(ref frames) = frames {
+        if let Err(e) = fs::create_dir_all(frames.dir()) {
+            eprintln!("failed to create '{}': {}", frames.dir().display(), e);
+            return Err(EXIT_SETUP);
+        }
+    }
+    let stdout = io::stdout();
+    headless::run(state, &mut script, ticks, stdout.lock(), (frames, outputs), stats)
+        .map_err(|e| {
+            eprintln!("{}", e);
+            exit_code(&e)
+        })
+}
+
+fn read_script(filename: &str) -> Result<headless::Script, String> {
+    fs::read_to_string(filename)
+        .map_err(|e| e.to_string())
+        .and_then(|text| headless::Script::parse(&text))
+}
+
+// Run the game, in a window or not, with the replay's inputs if any.
+fn play<S: GameState>(mut state: S,
+                      title: &str,
+                      options: Options,
+                      replay: Option<Replay>) -> Result<(), u8>
+{
+    // frames are only timed if the timings are going to be shown or saved.
+    let mut stats = if options.overlay || options.stats.is_some() {
+        Some(Stats::new(options.overlay))
+    } else {
+        None
+    };
+    let stats_file = options.stats.clone();
+    let wav = match options.wav {
+        Some(ref filename) => match WavWriter::create(filename, options.timing.tick_rate) {
+            Ok(wav) => Some(wav),
+            Err(e) => {
+                eprintln!("failed to create '{}': {}", filename, e);
+                return Err(EXIT_SETUP);
+            },
+        },
+        None => None,
+    };
+    let publisher = match options.publish {
+        Some(ref address) => match Publisher::bind(address) {
+            Ok(publisher) => Some(publisher),
+            Err(e) => {
+                eprintln!("failed to publish on '{}': {}", address, e);
+                return Err(EXIT_SETUP);
+            },
+        },
+        None => None,
+    };
+    let outputs = (wav, publisher);
+
+    let result = match options.headless {
+        Some(ticks) => {
+            let script = match (replay, &options.script) {
+                (Some(replay), _) => replay.script,
+                (None, Some(filename)) => match read_script(filename) {
+                    Ok(script) => script,
+                    Err(e) => {
+                        eprintln!("failed to read script '{}': {}", filename, e);
+                        return Err(EXIT_SETUP);
+                    },
+                },
+                (None, None) => headless::Script::empty(),
+            };
+            run_headless(&mut state, ticks, script, options.frames, outputs, stats.as_mut())
+        },
+        None => match replay {
+            Some(replay) => {
+                let state = Replaying::new(state, replay.script);
+                run_interactive(state, title, &options, outputs, stats.as_mut())
+            },
+            None => run_interactive(state, title, &options, outputs, stats.as_mut()),
+        },
+    };
+
+    if let (Some(stats), Some(filename)) = (stats, stats_file) {
+        eprint!("{}", stats.summary());
+        let written = File::create(&filename)
+            .and_then(|file| {
+                let mut out = io::BufWriter::new(file);
+                stats.write_csv(&mut out)?;
+                out.flush()
+            });
+        if let Err(e) = written {
+            eprintln!("failed to write timings to '{}': {}", filename, e);
+            return result.and(Err(EXIT_OUTPUT));
+        }
+    }
+    result
+}
+
+// Run the game in a window, or in the terminal. On error, the window is
+// closed, or the terminal restored, and the backend shut down before the
+// error is shown.
+//
+fn run_interactive<S: GameState>(mut state: S,
+                                 title: &str,
+                                 options: &Options,
+                                 outputs: Outputs,
+                                 stats: Option<&mut Stats>) -> Result<(), u8>
+{
+    let mut debugger = options.debug.map(Debugger::new);
+    let result = if options.terminal {
+        // there's no vsync to wait for in a terminal.
+        let timing = Timing {
+            vsync: false,
+            ..options.timing
+        };
+        // SDL is only needed for the sound, which is opened before the
+        // terminal is taken over, so that any error can still be read.
+        let speaker = if options.sound {
+            sdl2::init()
+                .and_then(|sdl_context| Speaker::open(&sdl_context))
+                .map_err(|e| eprintln!("failed to open audio, playing without sound: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        let (renderer, mut input) = match terminal::init(options.two_players, options.config.colors) {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                eprintln!("failed to set up the terminal: {}", e);
+                return Err(EXIT_SETUP);
+            },
+        };
+        let mut renderer = (renderer, (speaker, outputs));
+        game::game_loop(&mut state, &mut renderer, &mut input,
+                        Pace::RealTime(timing), stats, debugger.as_mut(), options.snapshots.as_ref())
+    } else {
+        let window = window::game_init(title,
+                                       options.timing.vsync,
+                                       options.two_players,
+                                       options.display,
+                                       options.sound,
+                                       &options.config);
+        let (renderer, mut input) = match window {
+            Ok(window) => window,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(EXIT_SETUP);
+            },
+        };
+        let mut renderer = (renderer, outputs);
+        game::game_loop(&mut state, &mut renderer, &mut input,
+                        Pace::RealTime(options.timing), stats, debugger.as_mut(),
+                        options.snapshots.as_ref())
+    };
+    drop(state);
+    result.map_err(|e| {
+        eprintln!("{}", e);
+        exit_code(&e)
+    })
+}
+
+fn run<S: GameState>(mut state: S,
+                     title: &str,
+                     mut options: Options,
+                     source: Source,
+                     replay: Option<Replay>,
+                     resume: Option<Snapshot>) -> Result<(), u8>
+{
+    let header = replay::Header {
+        source,
+        two_players: options.two_players || options.peer.is_some(),
+        difficulty: options.difficulty,
+        rules: match source {
+            Source::Native => Some(options.config.native),
+            Source::Lambda(_) => None,
+        },
+    };
+    if let Some(snapshot) = resume {
+        let filename = options.resume.as_deref().unwrap_or_default();
+        if let Err(e) = snapshot.check(&header) {
+            eprintln!("error: can't resume '{}': {}.", filename, e);
+            return Err(EXIT_USAGE);
+        }
+        if let Err(e) = state.load_state(&snapshot.state) {
+            eprintln!("failed to resume '{}': {}", filename, e);
+            return Err(EXIT_SETUP);
+        }
+    }
+    options.snapshots = Some(SnapshotFile::new(options.snapshot.clone(), header));
+    match options.record {
+        Some(ref filename) => {
+            match Recording::create(state, filename, header) {
+                Ok(recording) => play_online(recording, title, options, source, replay),
+                Err(e) => {
+                    eprintln!("failed to create replay '{}': {}", filename, e);
+                    Err(EXIT_SETUP)
+                },
             }
         },
+        None => play_online(state, title, options, source, replay),
+    }
+}
+
+// Play against the other player over the network, if there's one; the
+// handshake makes sure both play the same game.
+fn play_online<S: GameState>(state: S,
+                             title: &str,
+                             options: Options,
+                             source: Source,
+                             replay: Option<Replay>) -> Result<(), u8>
+{
+    let (stream, role) = match options.peer {
+        None => return play(state, title, options, replay),
+        Some(Peer::Host(ref address, port)) => (net::host(address, port), Role::Host),
+        Some(Peer::Guest(ref address)) => (net::connect(address), Role::Guest),
+    };
+    let stream = match stream {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("failed to reach the other player: {}", e);
+            return Err(EXIT_NETWORK);
+        },
     };
-    backend
+    let hello = match source {
+        Source::Native => Hello::native(options.difficulty, &options.config.native),
+        Source::Lambda(_) => Hello::lambda(source, options.difficulty),
+    };
+    match NetGame::new(state, stream, role, &hello, options.input_delay, net::TIMEOUT) {
+        Ok(game) => play(game, title, options, replay),
+        Err(e) => {
+            eprintln!("failed to start the network game: {}", e);
+            Err(EXIT_NETWORK)
+        },
+    }
+}
+
+// Check that the replay was recorded with the backend that's about to be
+// used; only the backend's kind must match, since a replay is also useful to
+// check whether a change to the source fixed something.
+//
+fn check_replay_source(replay: &Replay, source: Source) -> bool {
+    match (replay.header.source, source) {
+        (Source::Lambda(recorded), Source::Lambda(hash)) => {
+            if recorded != hash {
+                eprintln!("warning: the replay was recorded with a different lambda source file.");
+            }
+            true
+        },
+        (recorded, _) if recorded == source => true,
+        (recorded, _) => {
+            eprintln!("error: the replay was recorded with another backend ({}).", recorded);
+            false
+        },
+    }
 }
 
-fn main() {
-    if let Some(backend) = parse_args() {
-        match backend {
-            Backend::Native => {
-                let native_state = pong::State::new();
-                let (canvas, event_pump) = game::game_init("native pong");
-                game::game_loop(canvas, event_pump, native_state);
+fn main() -> ExitCode {
+    match start() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(code) => ExitCode::from(code),
+    }
+}
+
+// Everything main does, returning the exit code on failure.
+fn start() -> Result<(), u8> {
+    let mut options = match parse_args() {
+        Some(options) => options,
+        None => return Err(EXIT_USAGE),
+    };
+
+    let replay = match options.replay {
+        Some(ref filename) => match Replay::read(filename) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                eprintln!("failed to read replay '{}': {}", filename, e);
+                return Err(EXIT_SETUP);
             },
-            Backend::Lambda(filename) => {
-                let lambda_state = match lambda::State::new(&filename) {
-                    Ok(state) => state,
-                    Err(e) => {
-                        eprintln!("failed to create lambda state: '{}'", e);
-                        return;
-                    },
-                };
-                let (canvas, event_pump) = game::game_init("lambda pong");
-                game::game_loop(canvas, event_pump, lambda_state);
+        },
+        None => None,
+    };
+    if let Some(ref replay) = replay {
+        options.two_players = replay.header.two_players;
+        options.difficulty = replay.header.difficulty;
+        if let Some(rules) = replay.header.rules {
+            options.config.native = rules;
+        }
+    }
+    let resume = match options.resume {
+        Some(ref filename) => match Snapshot::read(filename.as_ref()) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                eprintln!("failed to read snapshot '{}': {}", filename, e);
+                return Err(EXIT_SETUP);
+            },
+        },
+        None => None,
+    };
+    // like a replay, a snapshot is played as it was saved.
+    if let Some(ref snapshot) = resume {
+        options.two_players = snapshot.header.two_players;
+        options.difficulty = snapshot.header.difficulty;
+        if let Some(rules) = snapshot.header.rules {
+            options.config.native = rules;
+        }
+    }
+    // over the network, both paddles are played, but each side only
+    // controls the right paddle's keys.
+    let two_players = options.two_players || options.peer.is_some();
+
+    match options.backend {
+        Backend::Spectate(ref address) => {
+            match Spectating::connect(address) {
+                Ok(spectating) => play(spectating, "pong spectator", options, None),
+                Err(e) => {
+                    eprintln!("failed to connect to '{}': {}", address, e);
+                    Err(EXIT_NETWORK)
+                },
             }
-        };
+        },
+        Backend::Native => {
+            if let Some(ref replay) = replay {
+                if !check_replay_source(replay, Source::Native) {
+                    return Err(EXIT_USAGE);
+                }
+            }
+            let native_state = pong::State::new(two_players,
+                                                options.config.native,
+                                                options.difficulty);
+            run(native_state, "native pong", options, Source::Native, replay, resume)
+        },
+        Backend::Lambda(ref filename) => {
+            let source = match Source::lambda_file(filename) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("failed to read '{}': {}", filename, e);
+                    return Err(EXIT_SETUP);
+                },
+            };
+            if let Some(ref replay) = replay {
+                if !check_replay_source(replay, source) {
+                    return Err(EXIT_USAGE);
+                }
+            }
+            let lambda_state = lambda::State::new(filename,
+                                                  options.output_format,
+                                                  two_players,
+                                                  options.difficulty);
+            let lambda_state = match lambda_state {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("failed to create lambda state: {}", e);
+                    if let lambda::Error::Terminated { .. } = e {
+                        eprintln!("check '{}' for syntax errors.", filename);
+                    }
+                    return Err(lambda_exit_code(&e));
+                },
+            };
+            let title = lambda_state.window_title().unwrap_or("lambda pong").to_string();
+            run(lambda_state, &title, options, source, replay, resume)
+        },
     }
 }
@@ -0,0 +1,715 @@
+// net.rs: two players over the network, each with their own copy of the game.
+//
+// Both backends are deterministic, so rather than sending the game's state,
+// the peers only exchange every tick's inputs, in lockstep: a tick only runs
+// once both peers' inputs for it are known. Each peer's inputs are delayed by
+// a few ticks, which gives them time to reach the other peer before they're
+// needed. The host plays the right paddle and the guest the left one.
+//
+// Everything is sent as lines of text. First, a handshake, which makes sure
+// both peers run the same game:
+//
+//     lambda-pong net 1
+//     backend lambda 9f3c0a61d2e4b857
+//     difficulty normal
+//     rules -
+//
+// Then one input per tick, by name, and, every so often, a hash of the
+// rectangles after a tick, to find out as soon as the peers' games drift
+// apart. Once the game is over, "rematch" asks for another one. A peer that
+// sends nothing for a while is taken to be gone.
+//
+//     up
+//     nothing
+//     hash 60 d5a4c2b1e0f98765
+//     rematch
+//
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    game::{Difficulty, Error, GameEvent, GameState, Inputs, UserInput},
+    pong::Tunables,
+    replay::{self, Source},
+};
+
+const MAGIC:&str = "lambda-pong net";
+const VERSION:u32 = 1;
+
+pub const DEFAULT_INPUT_DELAY:u32 = 2;
+
+// where the host waits for the guest: every interface.
+pub const DEFAULT_BIND:&str = "0.0.0.0";
+
+// how long to wait for the other peer before giving up on it.
+pub const TIMEOUT:Duration = Duration::from_secs(10);
+
+// how often the peers compare their games, in ticks.
+const HASH_INTERVAL:u64 = 60;
+
+const REMATCH:&str = "rematch";
+
+// far longer than any line the peers send, a backend's source path included.
+const MAX_LINE:u64 = 64 * 1024;
+
+/// Which side of the connection this is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// Waits for the guest, and plays the right paddle.
+    Host,
+    /// Connects to the host, and plays the left paddle.
+    Guest,
+}
+
+/// Everything both peers must agree on to play the same game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hello {
+    pub source: Source,
+    pub difficulty: Difficulty,
+    /// The native backend's parameters, or "-" for the lambda backend.
+    pub rules: String,
+}
+
+impl Hello {
+    pub fn native(difficulty: Difficulty, tunables: &Tunables) -> Hello {
+        Hello {
+            source: Source::Native,
+            difficulty,
+            rules: tunables.line(),
+        }
+    }
+
+    pub fn lambda(source: Source, difficulty: Difficulty) -> Hello {
+        Hello {
+            source,
+            difficulty,
+            rules: "-".to_string(),
+        }
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{} {}", MAGIC, VERSION)?;
+        writeln!(out, "backend {}", self.source)?;
+        writeln!(out, "difficulty {}", self.difficulty.name())?;
+        writeln!(out, "rules {}", self.rules)
+    }
+
+    fn read<R: BufRead>(reader: &mut R) -> Result<Hello, String> {
+        let mut header_line = |key: &str| {
+            match read_line(reader, MAX_LINE) {
+                Ok(Some(ref line)) if line.starts_with(key) => {
+                    Ok(line[key.len()..].trim().to_string())
+                },
+                Ok(_) => Err(format!("expected '{}' in the handshake", key)),
+                Err(e) => Err(e.to_string()),
+            }
+        };
+
+        let version = header_line(MAGIC)
+            .map_err(|_| "the other side isn't lambda pong".to_string())?;
+        if version.parse() != Ok(VERSION) {
+            return Err(format!("the other side speaks version '{}'", version));
+        }
+        let source = header_line("backend ")?;
+        let source = Source::parse(&source)
+            .ok_or(format!("invalid backend '{}'", source))?;
+        let difficulty = header_line("difficulty ")?;
+        let difficulty = Difficulty::from_name(&difficulty)
+            .ok_or(format!("invalid difficulty '{}'", difficulty))?;
+        let rules = header_line("rules ")?;
+        Ok(Hello { source, difficulty, rules })
+    }
+
+    // Check that the other side, with theirs, plays the same game.
+    fn check(&self, theirs: &Hello) -> Result<(), String> {
+        if theirs.source != self.source {
+            return Err(format!("the other side plays with another backend ({})", theirs.source));
+        }
+        if theirs.difficulty != self.difficulty {
+            return Err(format!("the other side plays at difficulty '{}'", theirs.difficulty.name()));
+        }
+        if theirs.rules != self.rules {
+            return Err(format!("the other side plays with other [native] parameters ({})", theirs.rules));
+        }
+        Ok(())
+    }
+}
+
+/// Wait for a guest to connect on the given local address and port.
+pub fn host(address: &str, port: u16) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind((address, port))?;
+    eprintln!("waiting for the other player on {} port {}...", address, port);
+    let (stream, address) = listener.accept()?;
+    eprintln!("{} connected.", address);
+    Ok(stream)
+}
+
+/// Connect to a host, given as host:port.
+pub fn connect(address: &str) -> io::Result<TcpStream> {
+    TcpStream::connect(address)
+}
+
+/// Read a line, without its line ending, refusing lines of more than max
+/// bytes rather than reading them all in. Returns None at the end of the
+/// stream.
+///
+pub fn read_line<R: BufRead>(reader: &mut R, max: u64) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.by_ref().take(max + 1).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    } else if line.len() as u64 > max {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("line longer than {} bytes", max)));
+    }
+    Ok(Some(line))
+}
+
+enum Message {
+    Input(UserInput),
+    Hash(u64, u64),
+    Rematch,
+}
+
+impl Message {
+    fn parse(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        let message = match (words.next()?, words.next(), words.next()) {
+            ("hash", Some(tick), Some(hash)) => {
+                Message::Hash(tick.parse().ok()?, u64::from_str_radix(hash, 16).ok()?)
+            },
+            (REMATCH, None, None) => Message::Rematch,
+            (input, None, None) => Message::Input(UserInput::from_name(input)?),
+            _ => return None,
+        };
+        match words.next() {
+            None => Some(message),
+            Some(_) => None,
+        }
+    }
+}
+
+// A hash of the rectangles, to compare games.
+fn hash_rects(rects: &[Rect]) -> u64 {
+    let mut bytes = Vec::with_capacity(rects.len() * 16);
+    for rect in rects {
+        bytes.extend_from_slice(&rect.x().to_le_bytes());
+        bytes.extend_from_slice(&rect.y().to_le_bytes());
+        bytes.extend_from_slice(&rect.width().to_le_bytes());
+        bytes.extend_from_slice(&rect.height().to_le_bytes());
+    }
+    replay::hash(&bytes)
+}
+
+/// Plays the state against another peer, taking the right paddle's input for
+/// this peer and getting the other's from the connection.
+///
+/// Only the host can pause and restart; either player quitting, or the
+/// connection being lost, ends the game for both.
+///
+pub struct NetGame<S: GameState> {
+    state: S,
+    role: Role,
+    // the other peer's lines, read from a thread so that waiting for them
+    // can time out, and doesn't block while the game is over.
+    lines: Receiver<io::Result<String>>,
+    writer: TcpStream,
+    timeout: Duration,
+    // this peer's inputs that have been sent but not run yet, and the other
+    // peer's that have been received, both starting at the next tick.
+    local: VecDeque<UserInput>,
+    remote: VecDeque<UserInput>,
+    tick: u64,
+    // hashes of the rectangles after some ticks, until they're compared.
+    local_hashes: VecDeque<(u64, u64)>,
+    remote_hashes: VecDeque<(u64, u64)>,
+    remote_rematch: bool,
+    // whether this peer asked for a rematch, and waits for the other one.
+    rematch_sent: bool,
+    over: bool,
+    quit: bool,
+    // why the other peer is gone, if it is.
+    left: Option<&'static str>,
+}
+
+impl<S: GameState> NetGame<S> {
+    /// Shake hands with the other peer, and start sending this peer's inputs
+    /// input_delay ticks ahead. Hearing nothing from the other peer for
+    /// timeout while waiting for it loses the connection.
+    ///
+    pub fn new(state: S,
+               stream: TcpStream,
+               role: Role,
+               hello: &Hello,
+               input_delay: u32,
+               timeout: Duration) -> Result<NetGame<S>, String> {
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream);
+        hello.write(&mut writer).map_err(|e| e.to_string())?;
+        hello.check(&Hello::read(&mut reader)?)?;
+        // from now on, the thread waits as long as it takes.
+        reader.get_ref().set_read_timeout(None).map_err(|e| e.to_string())?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in std::iter::from_fn(|| read_line(&mut reader, MAX_LINE).transpose()) {
+                let failed = line.is_err();
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let mut game = NetGame {
+            state,
+            role,
+            lines,
+            writer,
+            timeout,
+            local: VecDeque::new(),
+            remote: VecDeque::new(),
+            tick: 0,
+            local_hashes: VecDeque::new(),
+            remote_hashes: VecDeque::new(),
+            remote_rematch: false,
+            rematch_sent: false,
+            over: false,
+            quit: false,
+            left: None,
+        };
+        // nothing is pressed until the first inputs get through.
+        for _ in 0..input_delay {
+            game.local.push_back(UserInput::Nothing);
+            game.send(UserInput::Nothing.name());
+        }
+        Ok(game)
+    }
+
+    // Send a line to the other peer. Failing to means it's gone, which is
+    // found out when reading from it, after whatever it sent before leaving.
+    fn send(&mut self, line: &str) {
+        if self.left.is_none() {
+            let _ = writeln!(self.writer, "{}", line);
+        }
+    }
+
+    // Wait for the next message from the other peer, and handle it. Returns
+    // false if it's gone.
+    fn receive(&mut self) -> Result<bool, Error> {
+        if self.left.is_some() {
+            return Ok(false);
+        }
+        match self.lines.recv_timeout(self.timeout) {
+            Ok(line) => self.handle(line),
+            Err(RecvTimeoutError::Timeout) => Err(Error::Network("connection lost".to_string())),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.left = Some("OPPONENT LEFT");
+                Ok(false)
+            },
+        }
+    }
+
+    // Handle the messages the other peer already sent, without waiting for
+    // more.
+    fn receive_sent(&mut self) -> Result<(), Error> {
+        while self.left.is_none() {
+            match self.lines.try_recv() {
+                Ok(line) => {
+                    self.handle(line)?;
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.left = Some("OPPONENT LEFT"),
+            }
+        }
+        Ok(())
+    }
+
+    // Handle a line read from the other peer. Returns false if reading
+    // failed, and so it's gone.
+    fn handle(&mut self, line: io::Result<String>) -> Result<bool, Error> {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => {
+                self.left = Some("CONNECTION LOST");
+                return Ok(false);
+            },
+        };
+        match Message::parse(&line) {
+            Some(Message::Input(input)) => self.remote.push_back(input),
+            Some(Message::Hash(tick, hash)) => {
+                self.remote_hashes.push_back((tick, hash));
+                self.compare_hashes()?;
+            },
+            Some(Message::Rematch) => self.remote_rematch = true,
+            None => {
+                return Err(Error::Network(format!("unexpected message from the other side: '{}'",
+                                                  line.trim())));
+            },
+        }
+        Ok(true)
+    }
+
+    // The other peer's input for the next tick; Quit once it's gone.
+    fn remote_input(&mut self) -> Result<UserInput, Error> {
+        loop {
+            if let Some(input) = self.remote.pop_front() {
+                return Ok(input);
+            }
+            if !self.receive()? {
+                return Ok(UserInput::Quit);
+            }
+        }
+    }
+
+    fn compare_hashes(&mut self) -> Result<(), Error> {
+        while let (Some(&(tick, local)), Some(&(_, remote))) = (self.local_hashes.front(),
+                                                                self.remote_hashes.front()) {
+            if local != remote {
+                return Err(Error::Network(format!("out of sync with the other side at tick {}",
+                                                  tick)));
+            }
+            self.local_hashes.pop_front();
+            self.remote_hashes.pop_front();
+        }
+        Ok(())
+    }
+
+    // Ask for a rematch, which starts once the other peer wants one too. If
+    // it's gone instead, the game stays over.
+    fn rematch(&mut self) -> Result<(), Error> {
+        if !self.rematch_sent {
+            self.send(REMATCH);
+            self.rematch_sent = true;
+        }
+        self.receive_sent()?;
+        if !self.remote_rematch || self.left.is_some() {
+            return Ok(());
+        }
+        self.remote_rematch = false;
+        self.rematch_sent = false;
+        self.over = false;
+        self.state.update(Inputs {
+            player: UserInput::Restart,
+            second_player: UserInput::Nothing,
+        })
+    }
+}
+
+impl<S: GameState> Drop for NetGame<S> {
+    // lets the reading thread go.
+    fn drop(&mut self) {
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+}
+
+impl<S: GameState> GameState for NetGame<S> {
+    fn game_over(&mut self) -> Result<bool, Error> {
+        if self.over && self.rematch_sent {
+            self.rematch()?;
+        }
+        self.over = self.quit || self.left.is_some() || self.state.game_over()?;
+        Ok(self.over)
+    }
+
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+        let input = inputs.player;
+        if self.over {
+            // once over, the only way to go on is a rematch.
+            if input == UserInput::Restart {
+                self.rematch()?;
+            }
+            return Ok(());
+        }
+        // quitting is immediate here, and reaches the other peer later.
+        self.quit |= input == UserInput::Quit;
+        self.local.push_back(input);
+        self.send(input.name());
+
+        let local = self.local.pop_front().unwrap_or(UserInput::Nothing);
+        let remote = self.remote_input()?;
+        if remote == UserInput::Quit && self.left.is_none() {
+            self.left = Some("OPPONENT LEFT");
+        }
+        let (host, guest) = match self.role {
+            Role::Host => (local, remote),
+            Role::Guest => (remote, local),
+        };
+        let player = if guest == UserInput::Quit { UserInput::Quit } else { host };
+        self.state.update(Inputs {
+            player,
+            second_player: guest,
+        })?;
+
+        self.tick += 1;
+        if self.tick.is_multiple_of(HASH_INTERVAL) {
+            let hash = hash_rects(&self.state.get_rects()?);
+            self.send(&format!("hash {} {:016x}", self.tick, hash));
+            self.local_hashes.push_back((self.tick, hash));
+            self.compare_hashes()?;
+        }
+        Ok(())
+    }
+
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
+        self.state.get_rects()
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        self.state.get_text()
+    }
+
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        match self.left {
+            Some(left) => Ok(Some(left.to_string())),
+            None if self.rematch_sent => Ok(Some("WAITING FOR THE OTHER PLAYER".to_string())),
+            None => self.state.result(),
+        }
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        self.state.events()
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        self.state.scores()
+    }
+
+    fn dump_state(&mut self) -> Option<String> {
+        self.state.dump_state()
+    }
+
+    fn save_state(&mut self) -> Option<String> {
+        self.state.save_state()
+    }
+
+    // the other peer's game would be left behind.
+    fn load_state(&mut self, _saved: &str) -> Result<(), String> {
+        Err("a game can't be loaded over the network".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::pong;
+
+    fn inputs(player: UserInput) -> Inputs {
+        Inputs {
+            player,
+            second_player: UserInput::Nothing,
+        }
+    }
+
+    // Connect a host and a guest on localhost, each playing its own state.
+    fn connect_pair<S, F>(host_state: S, guest_state: S, hello: Hello, delay: u32, play: F)
+        -> (Result<Vec<Rect>, String>, Result<Vec<Rect>, String>)
+        where S: GameState + Send + 'static,
+              F: Fn(&mut NetGame<S>, Role) -> Result<(), Error> + Send + Copy + 'static
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host_hello = hello.clone();
+        let host = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut game = NetGame::new(host_state, stream, Role::Host, &host_hello, delay,
+                                        TIMEOUT)?;
+            play(&mut game, Role::Host).map_err(|e| e.to_string())?;
+            game.get_rects().map_err(|e| e.to_string())
+        });
+        let guest = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let mut game = NetGame::new(guest_state, stream, Role::Guest, &hello, delay,
+                                        TIMEOUT)?;
+            play(&mut game, Role::Guest).map_err(|e| e.to_string())?;
+            game.get_rects().map_err(|e| e.to_string())
+        });
+        (host.join().unwrap(), guest.join().unwrap())
+    }
+
+    fn native() -> pong::State {
+        pong::State::new(true, Tunables::default(), Difficulty::Normal)
+    }
+
+    fn hello() -> Hello {
+        Hello::native(Difficulty::Normal, &Tunables::default())
+    }
+
+    #[test]
+    fn peers_stay_in_sync() {
+        let (host, guest) = connect_pair(native(), native(), hello(), 3, |game, role| {
+            for tick in 0..200 {
+                let input = match (role, tick % 50 < 25) {
+                    (Role::Host, true) => UserInput::Up,
+                    (Role::Guest, true) => UserInput::Down,
+                    _ => UserInput::Nothing,
+                };
+                game.update(inputs(input))?;
+            }
+            Ok(())
+        });
+        let (host, guest) = (host.unwrap(), guest.unwrap());
+        assert_eq!(host, guest);
+
+        // both paddles moved, each as its own peer said.
+        let start = native().get_rects().unwrap();
+        assert!(host[0].y() > start[0].y());
+        assert!(host[1].y() < start[1].y());
+    }
+
+    #[test]
+    fn desync_is_detected() {
+        let other = Tunables {
+            step_size: 1,
+            ..Tunables::default()
+        };
+        let guest_state = pong::State::new(true, other, Difficulty::Normal);
+        let (host, guest) = connect_pair(native(), guest_state, hello(), 1, |game, _| {
+            for _ in 0..HASH_INTERVAL * 2 {
+                game.update(inputs(UserInput::Up))?;
+            }
+            Ok(())
+        });
+        let expected = format!("out of sync with the other side at tick {}", HASH_INTERVAL);
+        assert_eq!(host.unwrap_err(), expected);
+        assert_eq!(guest.unwrap_err(), expected);
+    }
+
+    #[test]
+    fn handshake_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            NetGame::new(native(), stream, Role::Host, &hello(), 1, TIMEOUT).err()
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let hard = Hello::native(Difficulty::Hard, &Tunables::default());
+        let guest = NetGame::new(native(), stream, Role::Guest, &hard, 1, TIMEOUT).err();
+        assert_eq!(host.join().unwrap(),
+                   Some("the other side plays at difficulty 'hard'".to_string()));
+        assert_eq!(guest, Some("the other side plays at difficulty 'normal'".to_string()));
+    }
+
+    #[test]
+    fn long_lines_are_refused() {
+        let mut reader = io::Cursor::new("up\r\nhash 60 00ff\nrematch");
+        assert_eq!(read_line(&mut reader, 12).unwrap(), Some("up".to_string()));
+        assert_eq!(read_line(&mut reader, 12).unwrap(), Some("hash 60 00ff".to_string()));
+        assert_eq!(read_line(&mut reader, 12).unwrap(), Some("rematch".to_string()));
+        assert_eq!(read_line(&mut reader, 12).unwrap(), None);
+
+        let handshake = format!("{} {}\nbackend {}\n", MAGIC, VERSION, "x".repeat(1 << 20));
+        assert_eq!(Hello::read(&mut io::Cursor::new(handshake)).err(),
+                   Some(format!("line longer than {} bytes", MAX_LINE)));
+    }
+
+    #[test]
+    fn quitting_ends_both_games() {
+        let (host, guest) = connect_pair(native(), native(), hello(), 2, |game, role| {
+            for tick in 0..10 {
+                let input = if role == Role::Guest && tick == 5 {
+                    UserInput::Quit
+                } else {
+                    UserInput::Nothing
+                };
+                game.update(inputs(input))?;
+                if game.game_over()? {
+                    let result = game.result()?;
+                    match role {
+                        // the guest's quit reaches the host input_delay ticks later.
+                        Role::Host => {
+                            assert_eq!(tick, 7);
+                            assert_eq!(result, Some("OPPONENT LEFT".to_string()));
+                        },
+                        Role::Guest => assert_eq!(tick, 5),
+                    }
+                    return Ok(());
+                }
+            }
+            panic!("the game didn't end");
+        });
+        host.unwrap();
+        guest.unwrap();
+    }
+
+    #[test]
+    fn silent_peer_is_given_up_on() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // the host shakes hands, then says nothing, without leaving.
+        let host = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            hello().write(&mut stream).unwrap();
+            thread::sleep(Duration::from_millis(500));
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let timeout = Duration::from_millis(50);
+        let mut game = NetGame::new(native(), stream, Role::Guest, &hello(), 1, timeout).unwrap();
+        assert_eq!(game.update(inputs(UserInput::Up)).map_err(|e| e.to_string()),
+                   Err("connection lost".to_string()));
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn rematch_waits_for_both() {
+        let short = Tunables {
+            win_score: 1,
+            ..Tunables::default()
+        };
+        let new_state = move || pong::State::new(true, short, Difficulty::Normal);
+        let hello = Hello::native(Difficulty::Normal, &short);
+        let (host, guest) = connect_pair(new_state(), new_state(), hello, 1, |game, role| {
+            // with both paddles out of the way, the first point ends the game.
+            let input = if role == Role::Host { UserInput::Up } else { UserInput::Down };
+            while !game.game_over()? {
+                game.update(inputs(input))?;
+            }
+            if role == Role::Guest {
+                // the host takes its time to ask for a rematch too.
+                game.update(inputs(UserInput::Restart))?;
+                assert!(game.game_over()?);
+                assert_eq!(game.result()?, Some("WAITING FOR THE OTHER PLAYER".to_string()));
+            } else {
+                thread::sleep(Duration::from_millis(100));
+                game.update(inputs(UserInput::Restart))?;
+            }
+            while game.game_over()? {
+                assert_eq!(game.left, None);
+                thread::sleep(Duration::from_millis(1));
+            }
+            for _ in 0..10 {
+                game.update(inputs(UserInput::Nothing))?;
+            }
+            Ok(())
+        });
+        let (host, guest) = (host.unwrap(), guest.unwrap());
+        assert_eq!(host, guest);
+    }
+
+    #[test]
+    fn messages() {
+        assert!(matches!(Message::parse("up\n"), Some(Message::Input(UserInput::Up))));
+        assert!(matches!(Message::parse("hash 60 00ff"), Some(Message::Hash(60, 0xff))));
+        assert!(matches!(Message::parse("rematch"), Some(Message::Rematch)));
+        assert!(Message::parse("jump").is_none());
+        assert!(Message::parse("up up").is_none());
+        assert!(Message::parse("hash 60").is_none());
+        assert!(Message::parse("").is_none());
+    }
+}
@@ -5,15 +5,187 @@ use sdl2::{
     rect::Rect,
 };
 
+use std::fmt;
+
+mod de_bruijn;
+
 enum Sign {
     Positive,
     Negative,
     Zero,
 }
 
+// maximum number of characters of the output shown in an error.
+const SNIPPET_LEN: usize = 24;
+
+/// Error found while parsing the interpreter's output.
+///
+/// The offset is always a byte offset into the output given to the public
+/// function that failed.
+///
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// Some construct was expected, but something else was found.
+    Unexpected {
+        offset: usize,
+        expected: &'static str,
+        snippet: String,
+    },
+    /// The output ended before the expected construct.
+    UnexpectedEnd {
+        offset: usize,
+        expected: &'static str,
+    },
+    /// The expected construct was found, but its value is not a valid one.
+    Invalid {
+        offset: usize,
+        expected: &'static str,
+        snippet: String,
+    },
+}
+
+impl ParseError {
+    // Every slice we parse is a suffix of the whole output, so while parsing,
+    // errors store how many bytes were left (i.e. the offset from the end).
+    // The public functions then turn that into an offset from the beginning.
+    //
+    fn offset_from_start(mut self, output: &str) -> ParseError {
+        match self {
+            ParseError::Unexpected { ref mut offset, .. }
+            | ParseError::UnexpectedEnd { ref mut offset, .. }
+            | ParseError::Invalid { ref mut offset, .. } => {
+                *offset = output.len().saturating_sub(*offset);
+            },
+        };
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Unexpected { offset, expected, snippet } => {
+                write!(f, "expected {} at byte {}, found `{}`", expected, offset, snippet)
+            },
+            ParseError::UnexpectedEnd { offset, expected } => {
+                write!(f, "expected {} at byte {}, but the output ended", expected, offset)
+            },
+            ParseError::Invalid { offset, expected, snippet } => {
+                write!(f, "invalid {} at byte {}: `{}`", expected, offset, snippet)
+            },
+        }
+    }
+}
+
+// Error for when s, a suffix of the output, doesn't begin with what we expected.
+fn unexpected(s: &str, expected: &'static str) -> ParseError {
+    if s.is_empty() {
+        ParseError::UnexpectedEnd {
+            offset: 0,
+            expected,
+        }
+    } else {
+        ParseError::Unexpected {
+            offset: s.len(),
+            expected,
+            snippet: snippet(s),
+        }
+    }
+}
+
+// Error for when s, a suffix of the output, begins with an invalid value.
+fn invalid(s: &str, expected: &'static str) -> ParseError {
+    ParseError::Invalid {
+        offset: s.len(),
+        expected,
+        snippet: snippet(s),
+    }
+}
+
+fn snippet(s: &str) -> String {
+    s.chars().take(SNIPPET_LEN).collect()
+}
+
+/// How the interpreter prints lambda terms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Named variables, e.g. `(\x y. x)`.
+    Named,
+    /// de Bruijn indices, e.g. `λλ2`.
+    DeBruijn,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "named" => Some(OutputFormat::Named),
+            "de-bruijn" => Some(OutputFormat::DeBruijn),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from some output: with named variables, the first
+    /// lambda symbol is followed by a variable name, which can't happen with
+    /// de Bruijn indices. Defaults to Named if there are no lambdas at all.
+    ///
+    pub fn detect(s: &str) -> OutputFormat {
+        let lambda_end = match s.find(['\\', 'λ']) {
+            None => return OutputFormat::Named,
+            Some(i) => i + s[i..].chars().next().map_or(1, char::len_utf8),
+        };
+        match s[lambda_end..].trim_start().chars().next() {
+            Some(c) if c.is_alphabetic() && c != 'λ' => OutputFormat::Named,
+            _ => OutputFormat::DeBruijn,
+        }
+    }
+
+    pub fn parse_church_bool(self, s: &str) -> Result<bool, ParseError> {
+        match self {
+            OutputFormat::Named => parse_church_bool(s),
+            OutputFormat::DeBruijn => de_bruijn::parse_church_bool(s),
+        }
+    }
+
+    pub fn parse_rect_list(self,
+                           s: &str,
+                           scaling_factor: i32,
+                           x_offset: i32,
+                           y_offset: i32) -> Result<Vec<Rect>, ParseError> {
+        match self {
+            OutputFormat::Named => parse_rect_list(s, scaling_factor, x_offset, y_offset),
+            OutputFormat::DeBruijn => de_bruijn::parse_rect_list(s, scaling_factor, x_offset, y_offset),
+        }
+    }
+
+    pub fn parse_string(self, s: &str) -> Result<String, ParseError> {
+        match self {
+            OutputFormat::Named => parse_string(s),
+            OutputFormat::DeBruijn => de_bruijn::parse_string(s),
+        }
+    }
+
+    pub fn parse_int_list(self, s: &str) -> Result<Vec<i32>, ParseError> {
+        match self {
+            OutputFormat::Named => parse_int_list(s),
+            OutputFormat::DeBruijn => de_bruijn::parse_int_list(s),
+        }
+    }
+
+    pub fn clni_to_int(self, s: &str) -> Result<i32, ParseError> {
+        match self {
+            OutputFormat::Named => clni_to_int(s),
+            OutputFormat::DeBruijn => de_bruijn::clni_to_int(s),
+        }
+    }
+}
+
 /// Convert a church boolean to a native boolean.
-pub fn parse_church_bool(s: &str) -> Result<bool, String> {
-    let (first_var_beg, first_var_end) = get_first_var_pos(&s)?;
+pub fn parse_church_bool(s: &str) -> Result<bool, ParseError> {
+    church_bool_prefix(s).map_err(|e| e.offset_from_start(s))
+}
+
+fn church_bool_prefix(s: &str) -> Result<bool, ParseError> {
+    let (first_var_beg, first_var_end) = get_first_var_pos(s)?;
     let first_var = &s[first_var_beg..first_var_end];
     let s = &s[first_var_end..];
 
@@ -22,12 +194,7 @@ pub fn parse_church_bool(s: &str) -> Result<bool, String> {
         sec_var_beg += 1;
     }
     if sec_var_beg == 0 {
-        if let Some(c) = s.chars().next() {
-            return Err(format!("expected space after first variable, found '{}'", c));
-        } else {
-            return Err("expected space after first variable, but no character was found"
-                       .to_string());
-        }
+        return Err(unexpected(s, "space after first variable"));
     }
     let sec_var_end = get_var_end(s, sec_var_beg)?;
 
@@ -36,26 +203,22 @@ pub fn parse_church_bool(s: &str) -> Result<bool, String> {
 
     match s.chars().next() {
         Some('.') => {},
-        Some(c) => return Err(format!("expected dot after second variable, found {}", c)),
-        None => return Err("expected dot after second variable, but no character was found"
-                           .to_string()),
+        _ => return Err(unexpected(s, "dot after second variable")),
     }
     let s = &s[1..]; // skip dot
-    let s = s.trim();
-    let mut body_end = s.len();
+    let s = s.trim_start();
+    let mut body_end = s.trim_end().len();
     while let Some(')') = s[..body_end].chars().next_back() {
         body_end -= 1;
     }
-    let s = &s[..body_end];
+    let body = &s[..body_end];
 
-    if s == first_var {
+    if body == first_var {
         Ok(true)
-    } else if s == second_var {
+    } else if body == second_var {
         Ok(false)
     } else {
-        Err(format!("lambda body {} is not equal to either first {} or second {} variable",
-                    s, first_var, second_var,
-        ))
+        Err(invalid(s, "boolean body (equal to either the first or second variable)"))
     }
 }
 
@@ -68,92 +231,165 @@ pub fn parse_church_bool(s: &str) -> Result<bool, String> {
 /// The x and y coordinates will also be added with their respective offsets
 /// afterwards.
 ///
-/// The list is parsed backwards, but that shouldn't make a difference.
+/// The rectangles are returned in reverse order, but that shouldn't make a
+/// difference.
 ///
 pub fn parse_rect_list(s: &str,
                        scaling_factor: i32,
                        x_offset: i32,
-                       y_offset:i32) -> Result<Vec<Rect>, String> {
+                       y_offset:i32) -> Result<Vec<Rect>, ParseError> {
+    rect_list_prefix(s, scaling_factor, x_offset, y_offset)
+        .map_err(|e| e.offset_from_start(s))
+}
 
-    // recursion base
-    if is_list_end(s) {
-        return Ok(Vec::new());
+// This is a loop rather than a recursion so that long lists can't overflow
+// the stack.
+//
+fn rect_list_prefix(s: &str,
+                    scaling_factor: i32,
+                    x_offset: i32,
+                    y_offset:i32) -> Result<Vec<Rect>, ParseError> {
+    let mut rect_list = Vec::new();
+    let mut s = s;
+
+    while !is_list_end(s) {
+        let rect_beg = skip_to_second_paren(s,
+                                            "open paren beginning a list of rectangles",
+                                            "second open paren in a list of rectangles")?;
+
+        let (rect, rest) = parse_rect(rect_beg, scaling_factor, x_offset, y_offset)?;
+        rect_list.push(rect);
+        s = rest.trim_start();
     }
+    rect_list.reverse();
+    Ok(rect_list)
+}
 
+// Given s beginning with an open paren, such as a church pair or tuple,
+// return the slice starting at the next open paren, i.e. its first element.
+//
+fn skip_to_second_paren<'a>(s: &'a str,
+                            open_paren: &'static str,
+                            second_paren: &'static str) -> Result<&'a str, ParseError> {
     match s.chars().next() {
         Some('(') => {},
-        _ => return Err("List of rectangles should begin with open paren".to_string()),
-    };
-    let first_num_beg = match s[1..].chars().position(|c| c == '(') {
-        None => return Err("List of rectangles doesn't have a second open paren".to_string()),
-        Some(i) => i,
+        _ => return Err(unexpected(s, open_paren)),
     };
+    match s[1..].find('(') {
+        None => Err(unexpected("", second_paren)),
+        Some(i) => Ok(&s[1 + i..]),
+    }
+}
 
-    let s = &s[first_num_beg..];
-    let s = s.trim();
+/// Parse a string; the string must be a list of chained church pairs, just like
+/// in parse_rect_list, where each element is a character code encoded in CLNI.
+///
+/// Unlike the list of rectangles, the string is parsed front to back, since
+/// the order of its characters obviously matters.
+///
+pub fn parse_string(s: &str) -> Result<String, ParseError> {
+    string_prefix(s).map_err(|e| e.offset_from_start(s))
+}
+
+fn string_prefix(s: &str) -> Result<String, ParseError> {
+    let mut string = String::new();
+    let mut s = s.trim_start();
+
+    while !is_list_end(s) {
+        let num = skip_to_second_paren(s,
+                                       "open paren beginning a string",
+                                       "second open paren in a string")?;
+        let (code, rest) = clni_prefix_to_int(num)?;
+
+        let c = match std::char::from_u32(code as u32) {
+            Some(c) if code >= 0 => c,
+            _ => return Err(invalid(num, "character code")),
+        };
+        string.push(c);
+        s = rest.trim_start();
+    }
+    Ok(string)
+}
 
-    let (rect, s) = parse_rect(s, scaling_factor, x_offset, y_offset)?;
-    let s = s.trim();
+/// Parse a list of non-negative integers; the list must be made of chained
+/// church pairs, just like in parse_string, where each element is encoded in
+/// CLNI. The integers are in the list's order.
+///
+pub fn parse_int_list(s: &str) -> Result<Vec<i32>, ParseError> {
+    int_list_prefix(s).map_err(|e| e.offset_from_start(s))
+}
 
-    let mut rect_list = parse_rect_list(s, scaling_factor, x_offset, y_offset)?;
-    rect_list.push(rect);
-    return Ok(rect_list);
+fn int_list_prefix(s: &str) -> Result<Vec<i32>, ParseError> {
+    let mut ints = Vec::new();
+    let mut s = s.trim_start();
+
+    while !is_list_end(s) {
+        let num = skip_to_second_paren(s,
+                                       "open paren beginning a list",
+                                       "second open paren in a list")?;
+        let (int, rest) = clni_prefix_to_int(num)?;
+        ints.push(int);
+        s = rest.trim_start();
+    }
+    Ok(ints)
 }
 
 fn parse_rect(s: &str,
               scaling_factor: i32,
               x_offset: i32,
-              y_offset: i32) -> Result<(Rect, &str), String> {
-    match s.chars().next() {
-        Some('(') => {},
-        None => return Err("Empty rectangle expression".to_string()),
-        Some(c) => return Err(format!("Rectangle expression doesn't start with open paren, but with `{}`", c)),
-    };
-    let first_num_beg = match s[1..].chars().position(|c| c == '(') {
-        None => return Err("Rectangle expression doesn't have a second open paren".to_string()),
-        Some(i) => i,
-    };
-    let s = &s[first_num_beg..];
-    let s = s.trim();
+              y_offset: i32) -> Result<(Rect, &str), ParseError> {
+    let rect_beg = s;
+    let s = skip_to_second_paren(s,
+                                 "open paren beginning a rectangle",
+                                 "second open paren in a rectangle")?;
 
     let (x, s) = clni_prefix_to_int(s)?;
-    let s = s.trim();
+    let s = s.trim_start();
 
     let (y, s) = clni_prefix_to_int(s)?;
-    let s = s.trim();
+    let s = s.trim_start();
 
     let (width, s) = clni_prefix_to_int(s)?;
-    let s = s.trim();
+    let s = s.trim_start();
 
     let (height, mut s) = clni_prefix_to_int(s)?;
     if let Some(')') = s.chars().next() {
         s = &s[1..];
     }
 
-    let x = x * scaling_factor + x_offset;
-    let y = y * scaling_factor + y_offset;
-    let width = (width * scaling_factor) as u32;
-    let height = (height * scaling_factor) as u32;
+    let rect = scaled_rect(x, y, width, height, scaling_factor, x_offset, y_offset)
+        .ok_or_else(|| invalid(rect_beg, "rectangle (coordinates overflow when scaled)"))?;
+    Ok((rect, s))
+}
 
-    Ok((Rect::new(x, y, width, height), s))
+// Build a rectangle as described in parse_rect_list, or None on overflow.
+fn scaled_rect(x: i32, y: i32, width: i32, height: i32,
+               scaling_factor: i32,
+               x_offset: i32,
+               y_offset: i32) -> Option<Rect> {
+    let scale = |n: i32, offset: i32| {
+        n.checked_mul(scaling_factor)
+            .and_then(|n| n.checked_add(offset))
+    };
+    Some(Rect::new(scale(x, x_offset)?,
+                   scale(y, y_offset)?,
+                   scale(width, 0)? as u32,
+                   scale(height, 0)? as u32))
 }
 
 /// For non-negative values only.
-pub fn clni_to_int(s: &str) -> Result<i32, String> {
-    let (num, _) = clni_prefix_to_int(&s)?;
+pub fn clni_to_int(s: &str) -> Result<i32, ParseError> {
+    let (num, _) = clni_prefix_to_int(s).map_err(|e| e.offset_from_start(s))?;
     Ok(num)
 }
 
 // Convert a non-negative number in CLNI at the beginning of the string s,
 // returning its value and the slice containing the rest of the string.
 //
-fn clni_prefix_to_int(s: &str) -> Result<(i32, &str), String> {
+fn clni_prefix_to_int(s: &str) -> Result<(i32, &str), ParseError> {
     match s.chars().next() {
-        Some(c) if c != '(' => {
-            return Err(format!("CLNI int at `{}` doesn't begin with open paren, but `{}`", s, c));
-        }
-        None => return Err("CLNI integer is empty'".to_string()),
-        _ => {},
+        Some('(') => {},
+        _ => return Err(unexpected(s, "open paren beginning a CLNI integer")),
     };
 
     let sign = clni_prefix_sign(s)?;
@@ -166,7 +402,7 @@ fn clni_prefix_to_int(s: &str) -> Result<(i32, &str), String> {
             Some('\\') => backslash_count += 1,
             Some('(') => paren_levelcount += 1,
             Some(')') => paren_levelcount -= 1,
-            None => return Err("unfinished CLNI integer!".to_string()),
+            None => return Err(unexpected("", "close paren ending a CLNI integer")),
             _ => {},
         };
         if paren_levelcount == 0 {
@@ -182,7 +418,7 @@ fn clni_prefix_to_int(s: &str) -> Result<(i32, &str), String> {
     Ok((num, chars.as_str()))
 }
 
-fn clni_prefix_sign(s: &str) -> Result<Sign, String> {
+fn clni_prefix_sign(s: &str) -> Result<Sign, ParseError> {
     // Any positive number will be of form
     //      (\x u. u x ...)
     //      (\x1 u. u x1 ...)
@@ -199,7 +435,7 @@ fn clni_prefix_sign(s: &str) -> Result<Sign, String> {
     loop {
         match chars.next() {
             Some('\\') => break,
-            None => return Err("CLNI integer has no lambda terms in it!".to_string()),
+            None => return Err(unexpected("", "lambda term in a CLNI integer")),
             _ => {},
         };
     }
@@ -208,7 +444,7 @@ fn clni_prefix_sign(s: &str) -> Result<Sign, String> {
         match chars.next() {
             Some(' ') => space_count += 1,
             Some('.') => break,
-            None => return Err("CLNI integer has unfinished lambda term in it!".to_string()),
+            None => return Err(unexpected("", "dot ending a lambda term's variables")),
             _ => {},
         };
     };
@@ -242,7 +478,7 @@ fn is_list_end(s: &str) -> bool {
     if s.starts_with("nil") || s.starts_with("false") {
         return true;
     }
-    if let Ok(b) = parse_church_bool(s) {
+    if let Ok(b) = church_bool_prefix(s) {
         if !b {
             return true;
         }
@@ -250,9 +486,9 @@ fn is_list_end(s: &str) -> bool {
     false
 }
 
-fn get_first_var_pos(s: &str) -> Result<(usize, usize), String> {
-    let var_beg = match s.chars().position(|c| c == '\\') {
-        None => return Err("no backslash (lambda symbol) found".to_string()),
+fn get_first_var_pos(s: &str) -> Result<(usize, usize), ParseError> {
+    let var_beg = match s.find('\\') {
+        None => return Err(unexpected(s, "backslash (lambda symbol)")),
         Some(i) => i + 1,
     };
     let var_end = get_var_end(s, var_beg)?;
@@ -262,9 +498,9 @@ fn get_first_var_pos(s: &str) -> Result<(usize, usize), String> {
 // This function assumes var_beg is a valid index and the beginning of the
 // variable whose end we seek.
 //
-fn get_var_end(s: &str, var_beg: usize) -> Result<usize, String> {
-    match s[var_beg..].chars().position(|c| !c.is_alphanumeric()) {
-        None => return Err("unfinished lambda term".to_string()),
+fn get_var_end(s: &str, var_beg: usize) -> Result<usize, ParseError> {
+    match s[var_beg..].find(|c: char| !c.is_alphanumeric()) {
+        None => Err(unexpected("", "end of a lambda term's variable")),
         Some(i) => Ok(i + var_beg),
     }
 }
@@ -312,10 +548,12 @@ mod tests {
     fn church_bool_true1() {
         assert_eq!(parse_church_bool("(\\x y. x)"), Ok(true));
     }
+
     #[test]
     fn church_bool_true2() {
         assert_eq!(parse_church_bool("(\\apple orange. apple)"), Ok(true));
     }
+
     #[test]
     fn church_bool_true3() {
         assert_eq!(parse_church_bool("(\\x1 y1. x1)"), Ok(true));
@@ -325,10 +563,12 @@ mod tests {
     fn church_bool_false1() {
         assert_eq!(parse_church_bool("(\\x y. y)"), Ok(false));
     }
+
     #[test]
     fn church_bool_false2() {
         assert_eq!(parse_church_bool("(\\blue green. green)"), Ok(false));
     }
+
     #[test]
     fn church_bool_false3() {
         assert_eq!(parse_church_bool("(\\x2 y2. y2)"), Ok(false));
@@ -394,4 +634,323 @@ mod tests {
         expected.push(rect1);
         assert_eq!(parse_rect_list(s, 1, 0, 0), Ok(expected));
     }
+
+    // Build a non-negative CLNI integer the way the interpreter prints it.
+    fn clni(n: u32, var: &str) -> String {
+        if n == 0 {
+            return format!("(\\{}. {})", var, var);
+        }
+        let mut s = format!("(\\{} u0. u0 ", var);
+        for i in 1..n {
+            s += &format!("(\\{}u{}. {}u{} ", var, i, var, i);
+        }
+        s += var;
+        s += &")".repeat(n as usize);
+        s
+    }
+
+    #[test]
+    fn test_string1() {
+        let s = format!("(\\z. z {} (\\z1. z1 {} nil))", clni(72, "x"), clni(105, "y"));
+        assert_eq!(parse_string(&s), Ok("Hi".to_string()));
+    }
+
+    #[test]
+    fn test_string_empty() {
+        assert_eq!(parse_string("nil"), Ok(String::new()));
+        assert_eq!(parse_string("(\\x y. y)"), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_int_list() {
+        let s = format!("(\\z. z {} (\\z1. z1 {} nil))", clni(2, "x"), clni(0, "y"));
+        assert_eq!(parse_int_list(&s), Ok(vec![2, 0]));
+        assert_eq!(parse_int_list("nil"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_string_invalid() {
+        let s = format!("(\\z. z {} nil)", "(\\x. x (\\u. u))");
+        assert!(parse_string(&s).is_err());
+    }
+
+    #[test]
+    fn error_offset_bool() {
+        let e = parse_church_bool("(\\x y. z)").unwrap_err();
+        assert_eq!(e, ParseError::Invalid {
+            offset: 7,
+            expected: "boolean body (equal to either the first or second variable)",
+            snippet: "z)".to_string(),
+        });
+    }
+
+    #[test]
+    fn error_offset_unfinished_int() {
+        let s = "(\\x u. u x";
+        let e = clni_to_int(s).unwrap_err();
+        assert_eq!(e, ParseError::UnexpectedEnd {
+            offset: s.len(),
+            expected: "close paren ending a CLNI integer",
+        });
+    }
+
+    #[test]
+    fn error_offset_rect_list() {
+        let s = "(\\z. z (\\f. f (\\x. x) oops) nil)";
+        match parse_rect_list(s, 1, 0, 0) {
+            Err(ParseError::Unexpected { offset, snippet, .. }) => {
+                assert_eq!(offset, s.find("oops").unwrap());
+                assert!(snippet.starts_with("oops"));
+            },
+            other => panic!("expected an Unexpected error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(OutputFormat::detect("(\\x u. u x)"), OutputFormat::Named);
+        assert_eq!(OutputFormat::detect("(λx. x)"), OutputFormat::Named);
+        assert_eq!(OutputFormat::detect("λλ1 2"), OutputFormat::DeBruijn);
+        assert_eq!(OutputFormat::detect("(\\ \\ 1 2)"), OutputFormat::DeBruijn);
+        assert_eq!(OutputFormat::detect("nil"), OutputFormat::Named);
+    }
+
+    // Property tests, and random inputs that must not make the decoders
+    // panic.
+    //
+    // These use a tiny xorshift generator rather than an extra crate; it's
+    // seeded with fixed values, so failures are reproducible.
+    //
+    const PROPERTY_ITERATIONS: usize = 500;
+    const RANDOM_ITERATIONS: usize = 5000;
+
+    pub(super) struct Rng(pub(super) u64);
+
+    impl Rng {
+        pub(super) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(super) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        pub(super) fn range(&mut self, lo: i32, hi: i32) -> i32 {
+            lo + self.below((hi - lo + 1) as usize) as i32
+        }
+
+        pub(super) fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    // A variable name, which must be distinct from `other`.
+    fn gen_var(rng: &mut Rng, other: &str) -> String {
+        loop {
+            let prefix = rng.pick(&["x", "u", "f", "apple", "z", "v9", "Ab"]);
+            let var = match rng.below(3) {
+                0 => prefix.to_string(),
+                _ => format!("{}{}", prefix, rng.below(100)),
+            };
+            if var != other {
+                return var;
+            }
+        }
+    }
+
+    // Whitespace inside a lambda term, where the interpreter puts one space.
+    fn gen_space(rng: &mut Rng) -> String {
+        " ".repeat(1 + rng.below(3))
+    }
+
+    // Whitespace between the elements of a list.
+    fn gen_sep(rng: &mut Rng) -> String {
+        let mut sep = gen_space(rng);
+        for _ in 0..rng.below(3) {
+            sep.push_str(rng.pick(&[" ", "\t", "\r"]));
+        }
+        sep
+    }
+
+    fn gen_clni(rng: &mut Rng, n: i32) -> String {
+        let x = gen_var(rng, "");
+        if n == 0 {
+            return format!("(\\{}.{}{})", x, gen_space(rng), x);
+        }
+        let mut s;
+        if n > 0 {
+            let u = gen_var(rng, &x);
+            s = format!("(\\{}{}{}.{}{}{}", x, gen_space(rng), u, gen_space(rng), u, gen_space(rng));
+            for i in 1..n {
+                let u = format!("{}{}", gen_var(rng, &x), i);
+                s += &format!("(\\{}.{}{}{}", u, gen_space(rng), u, gen_space(rng));
+            }
+            s += &x;
+            s += &")".repeat(n as usize);
+        } else {
+            s = format!("(\\{}.{}{}", x, gen_space(rng), x);
+            for _ in 0..-n {
+                let u = gen_var(rng, "");
+                s += &format!("{}(\\{}.{}{})", gen_space(rng), u, gen_space(rng), u);
+            }
+            s += ")";
+        }
+        s
+    }
+
+    fn gen_bool(rng: &mut Rng, b: bool) -> String {
+        let first = gen_var(rng, "");
+        let second = gen_var(rng, &first);
+        let body = if b { &first } else { &second };
+        format!("(\\{}{}{}.{}{})", first, gen_space(rng), second, gen_space(rng), body)
+    }
+
+    fn gen_list_end(rng: &mut Rng) -> String {
+        match rng.below(3) {
+            0 => "nil".to_string(),
+            1 => "false".to_string(),
+            _ => gen_bool(rng, false),
+        }
+    }
+
+    // A church list of the already encoded elements.
+    fn gen_list(rng: &mut Rng, elems: &[String]) -> String {
+        let mut s = String::new();
+        for elem in elems {
+            let z = gen_var(rng, "");
+            s += &format!("(\\{}.{}{}{}{}{}", z, gen_space(rng), z, gen_sep(rng), elem, gen_sep(rng));
+        }
+        s += &gen_list_end(rng);
+        s += &")".repeat(elems.len());
+        s
+    }
+
+    fn gen_rect(rng: &mut Rng, nums: &[i32; 4]) -> String {
+        let f = gen_var(rng, "");
+        let mut s = format!("(\\{}.{}{}", f, gen_space(rng), f);
+        for n in nums.iter() {
+            s += &gen_sep(rng);
+            s += &gen_clni(rng, *n);
+        }
+        s + ")"
+    }
+
+    #[test]
+    fn prop_clni_roundtrip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..PROPERTY_ITERATIONS {
+            let n = rng.range(-40, 40);
+            let s = gen_clni(&mut rng, n);
+            let (num, rest) = clni_prefix_to_int(&s).unwrap();
+            assert_eq!(num, n, "input: {}", s);
+            assert_eq!(rest, "", "input: {}", s);
+        }
+    }
+
+    #[test]
+    fn prop_bool_roundtrip() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..PROPERTY_ITERATIONS {
+            let b = rng.below(2) == 0;
+            let s = gen_bool(&mut rng, b);
+            assert_eq!(parse_church_bool(&s), Ok(b), "input: {}", s);
+        }
+    }
+
+    #[test]
+    fn prop_rect_list_roundtrip() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..PROPERTY_ITERATIONS {
+            let scaling_factor = rng.range(1, 5);
+            let x_offset = rng.range(-100, 100);
+            let y_offset = rng.range(-100, 100);
+
+            let mut expected = Vec::new();
+            let mut elems = Vec::new();
+            for _ in 0..rng.below(5) {
+                let nums = [rng.range(-30, 30), rng.range(-30, 30),
+                            rng.range(1, 30), rng.range(1, 30)];
+                elems.push(gen_rect(&mut rng, &nums));
+                expected.push(Rect::new(nums[0] * scaling_factor + x_offset,
+                                        nums[1] * scaling_factor + y_offset,
+                                        (nums[2] * scaling_factor) as u32,
+                                        (nums[3] * scaling_factor) as u32));
+            }
+            expected.reverse();
+
+            let s = gen_list(&mut rng, &elems);
+            assert_eq!(parse_rect_list(&s, scaling_factor, x_offset, y_offset),
+                       Ok(expected),
+                       "input: {}", s);
+        }
+    }
+
+    #[test]
+    fn prop_string_roundtrip() {
+        let mut rng = Rng(0x0123_4567_89ab_cdef);
+        for _ in 0..PROPERTY_ITERATIONS {
+            let expected: String = (0..rng.below(8))
+                .map(|_| rng.range(32, 126) as u8 as char)
+                .collect();
+            let elems: Vec<String> = expected.chars()
+                .map(|c| gen_clni(&mut rng, c as i32))
+                .collect();
+            let s = gen_list(&mut rng, &elems);
+            assert_eq!(parse_string(&s), Ok(expected), "input: {}", s);
+        }
+    }
+
+    // Feed the output to every decoder; all we care about is that none panics.
+    fn decode_all(s: &str) {
+        let _ = parse_church_bool(s);
+        let _ = clni_to_int(s);
+        let _ = parse_string(s);
+        let _ = parse_int_list(s);
+        let _ = parse_rect_list(s, 5, 200, 150);
+        let _ = parse_rect_list(s, i32::MAX, i32::MAX, i32::MIN);
+    }
+
+    #[test]
+    fn random_bytes_dont_panic() {
+        let mut rng = Rng(0x5851_f42d_4c95_7f2d);
+        for _ in 0..RANDOM_ITERATIONS {
+            let len = rng.below(64);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            decode_all(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    #[test]
+    fn random_tokens_dont_panic() {
+        let tokens = ["(", ")", "\\", ".", " ", "x", "u1", "nil", "false", "λ", "é", "\t", "(\\x. x)"];
+        let mut rng = Rng(0x1405_7b7e_f767_814f);
+        for _ in 0..RANDOM_ITERATIONS {
+            let s: String = (0..rng.below(40)).map(|_| rng.pick(&tokens)).collect();
+            decode_all(&s);
+        }
+    }
+
+    #[test]
+    fn mutated_outputs_dont_panic() {
+        let mut rng = Rng(0xa076_1d64_78bd_642f);
+        for _ in 0..RANDOM_ITERATIONS {
+            let nums = [rng.range(-20, 20), rng.range(-20, 20), rng.range(0, 20), rng.range(0, 20)];
+            let rect = gen_rect(&mut rng, &nums);
+            let valid = gen_list(&mut rng, &[rect]);
+
+            let mut chars: Vec<char> = valid.chars().collect();
+            for _ in 0..1 + rng.below(4) {
+                let i = rng.below(chars.len() + 1);
+                match rng.below(3) {
+                    0 if i < chars.len() => { chars.remove(i); },
+                    1 => chars.insert(i, rng.pick(&["(", ")", "\\", ".", " ", "λ"]).chars().next().unwrap()),
+                    _ => chars.truncate(i),
+                }
+            }
+            decode_all(&chars.into_iter().collect::<String>());
+        }
+    }
 }
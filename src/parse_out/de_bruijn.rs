@@ -0,0 +1,438 @@
+// for parsing output printed with de Bruijn indices rather than named
+// variables, e.g. `λλ2` instead of `(\x y. x)`.
+//
+// Indices start at 1, so the innermost lambda's variable is 1, the one
+// enclosing it is 2, and so forth. Both `λ` and `\` are accepted as the
+// lambda symbol, optionally followed by a dot.
+//
+// Unlike the named format, the output is first parsed into a term, which is
+// then matched against the expected encoding. Nothing here recurses, so
+// integers and lists can be as long as the output goes; only terms nested
+// more than MAX_DEPTH deep off their spines are refused.
+//
+
+use sdl2::{
+    rect::Rect,
+};
+
+use super::{ParseError, unexpected, invalid, scaled_rect};
+
+// far more than any output we decode needs. Lambda bodies, the functions
+// applied and their last arguments are the spines integers and lists grow
+// along, so they don't count; a list of rectangles is at most 4 deep.
+const MAX_DEPTH: usize = 2000;
+
+struct Term<'a> {
+    // the output, starting where this term begins; used for errors.
+    at: &'a str,
+    kind: Kind<'a>,
+    // how deeply the term is nested off its spines, counting itself.
+    depth: usize,
+    // for an application, the deepest of its arguments so far; they leave
+    // the spine once another argument follows.
+    arg_depth: usize,
+}
+
+// Boxed terms are dropped recursively, which would overflow the stack on a
+// long integer or list, so their children are taken out one by one instead.
+impl Drop for Term<'_> {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        let mut kind = std::mem::replace(&mut self.kind, Kind::Var(0));
+        loop {
+            match kind {
+                Kind::Var(_) | Kind::Name(_) => {},
+                Kind::Lam(body) => pending.push(body),
+                Kind::App(f, arg) => {
+                    pending.push(f);
+                    pending.push(arg);
+                },
+            };
+            match pending.pop() {
+                Some(mut t) => kind = std::mem::replace(&mut t.kind, Kind::Var(0)),
+                None => break,
+            };
+        }
+    }
+}
+
+enum Kind<'a> {
+    Var(usize),
+    // free variables, which some interpreters print for known definitions,
+    // such as `nil`.
+    Name(&'a str),
+    Lam(Box<Term<'a>>),
+    App(Box<Term<'a>>, Box<Term<'a>>),
+}
+
+/// Convert a church boolean to a native boolean.
+pub fn parse_church_bool(s: &str) -> Result<bool, ParseError> {
+    parse_whole(s)
+        .and_then(|t| term_to_bool(&t))
+        .map_err(|e| e.offset_from_start(s))
+}
+
+/// Parse a list of rectangles; see parse_out::parse_rect_list.
+pub fn parse_rect_list(s: &str,
+                       scaling_factor: i32,
+                       x_offset: i32,
+                       y_offset:i32) -> Result<Vec<Rect>, ParseError> {
+    let rect_list = parse_whole(s).and_then(|t| {
+        let mut rect_list = Vec::new();
+        for elem in list_elems(&t)? {
+            rect_list.push(term_to_rect(elem, scaling_factor, x_offset, y_offset)?);
+        }
+        Ok(rect_list)
+    });
+    let mut rect_list = rect_list.map_err(|e| e.offset_from_start(s))?;
+    // same order as in the named format.
+    rect_list.reverse();
+    Ok(rect_list)
+}
+
+/// Parse a string; see parse_out::parse_string.
+pub fn parse_string(s: &str) -> Result<String, ParseError> {
+    let string = parse_whole(s).and_then(|t| {
+        let mut string = String::new();
+        for elem in list_elems(&t)? {
+            let code = term_to_int(elem)?;
+            match std::char::from_u32(code as u32) {
+                Some(c) if code >= 0 => string.push(c),
+                _ => return Err(invalid(elem.at, "character code")),
+            };
+        }
+        Ok(string)
+    });
+    string.map_err(|e| e.offset_from_start(s))
+}
+
+/// Parse a list of integers; see parse_out::parse_int_list.
+pub fn parse_int_list(s: &str) -> Result<Vec<i32>, ParseError> {
+    parse_whole(s)
+        .and_then(|t| list_elems(&t)?.into_iter().map(term_to_int).collect())
+        .map_err(|e| e.offset_from_start(s))
+}
+
+/// Convert a CLNI integer.
+pub fn clni_to_int(s: &str) -> Result<i32, ParseError> {
+    parse_whole(s)
+        .and_then(|t| term_to_int(&t))
+        .map_err(|e| e.offset_from_start(s))
+}
+
+fn is_lambda(c: char) -> bool {
+    c == 'λ' || c == '\\'
+}
+
+// A term made of the given parts, if it isn't nested too deeply.
+fn node<'a>(at: &'a str, kind: Kind<'a>) -> Result<Term<'a>, ParseError> {
+    let (depth, arg_depth) = match kind {
+        Kind::Var(_) | Kind::Name(_) => (1, 0),
+        Kind::Lam(ref body) => (body.depth, 0),
+        Kind::App(ref f, ref arg) => {
+            (f.depth.max(arg.depth).max(1 + f.arg_depth), f.arg_depth.max(arg.depth))
+        },
+    };
+    if depth > MAX_DEPTH {
+        return Err(invalid(at, "term (nested too deeply)"));
+    }
+    Ok(Term { at, kind, depth, arg_depth })
+}
+
+// A paren or lambda whose term hasn't been parsed yet, with the application
+// it's an argument of, if any.
+struct Open<'a> {
+    at: &'a str,
+    is_lambda: bool,
+    applied_to: Option<Term<'a>>,
+}
+
+// f applied to arg, or arg itself if there's no f.
+fn apply<'a>(f: Option<Term<'a>>, arg: Term<'a>) -> Result<Term<'a>, ParseError> {
+    match f {
+        None => Ok(arg),
+        Some(f) => node(f.at, Kind::App(Box::new(f), Box::new(arg))),
+    }
+}
+
+// Parse s, which must consist of a single term.
+//
+// A term is one or more atoms, applied to each other; an atom is an index, a
+// name, a term in parens, or a lambda, whose body goes on for as long as it
+// can. Rather than recursing, the parens and lambdas we're in are kept on a
+// stack, each with the application before it, while the innermost one's
+// application is built in term.
+//
+fn parse_whole(s: &str) -> Result<Term<'_>, ParseError> {
+    let mut stack: Vec<Open> = Vec::new();
+    let mut term: Option<Term> = None;
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start();
+        match rest.chars().next() {
+            Some(c) if c == '(' || is_lambda(c) => {
+                stack.push(Open {
+                    at: rest,
+                    is_lambda: c != '(',
+                    applied_to: term.take(),
+                });
+                rest = rest[c.len_utf8()..].trim_start();
+                if c != '(' {
+                    rest = rest.strip_prefix('.').unwrap_or(rest);
+                }
+            },
+            Some(c) if c.is_ascii_digit() => {
+                let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let var = match rest[..end].parse::<usize>() {
+                    Ok(i) if i > 0 => node(rest, Kind::Var(i))?,
+                    _ => return Err(invalid(rest, "de Bruijn index (starting from 1)")),
+                };
+                term = Some(apply(term, var)?);
+                rest = &rest[end..];
+            },
+            Some(c) if c.is_alphabetic() => {
+                let end = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+                term = Some(apply(term, node(rest, Kind::Name(&rest[..end]))?)?);
+                rest = &rest[end..];
+            },
+            c @ None | c @ Some(')') => {
+                // the innermost term ends here, and so does every lambda's body.
+                let body = term.take().ok_or_else(|| unexpected(rest, "de Bruijn term"))?;
+                let open = match stack.pop() {
+                    None if rest.is_empty() => return Ok(body),
+                    None => return Err(unexpected(rest, "end of output")),
+                    Some(open) => open,
+                };
+                let closed = if open.is_lambda {
+                    node(open.at, Kind::Lam(Box::new(body)))?
+                } else if c == Some(')') {
+                    rest = &rest[1..];
+                    // errors about the whole term point at the paren.
+                    let mut body = body;
+                    body.at = open.at;
+                    body
+                } else {
+                    return Err(unexpected(rest, "close paren"));
+                };
+                term = Some(apply(open.applied_to, closed)?);
+            },
+            _ => return Err(unexpected(rest, "de Bruijn term")),
+        };
+    }
+}
+
+// If t is `λ body`, return body.
+fn lam_body<'t, 'a>(t: &'t Term<'a>) -> Option<&'t Term<'a>> {
+    match t.kind {
+        Kind::Lam(ref body) => Some(body),
+        _ => None,
+    }
+}
+
+fn is_var(t: &Term, i: usize) -> bool {
+    match t.kind {
+        Kind::Var(j) => i == j,
+        _ => false,
+    }
+}
+
+// If t is the application of the variable i to some arguments, return them.
+fn var_args<'t, 'a>(t: &'t Term<'a>, i: usize) -> Option<Vec<&'t Term<'a>>> {
+    let mut args = Vec::new();
+    let mut t = t;
+    while let Kind::App(ref f, ref arg) = t.kind {
+        args.push(&**arg);
+        t = f;
+    }
+    if is_var(t, i) {
+        args.reverse();
+        Some(args)
+    } else {
+        None
+    }
+}
+
+fn term_to_bool(t: &Term) -> Result<bool, ParseError> {
+    match lam_body(t).and_then(lam_body) {
+        Some(body) if is_var(body, 2) => Ok(true),
+        Some(body) if is_var(body, 1) => Ok(false),
+        _ => Err(invalid(t.at, "Church boolean")),
+    }
+}
+
+// zero is `λ1`, positive numbers are `λλ1 (λ1 (λ1 ... n))`, where the
+// innermost n refers to the outermost lambda, and negative numbers are
+// `λ1 (λ1) (λ1) ...`.
+//
+fn term_to_int(t: &Term) -> Result<i32, ParseError> {
+    let err = || invalid(t.at, "CLNI integer");
+    let body = lam_body(t).ok_or_else(err)?;
+    if is_var(body, 1) {
+        return Ok(0);
+    }
+
+    if let Some(mut chain) = lam_body(body) {
+        let mut num = 1;
+        let mut depth = 2;
+        loop {
+            let args = var_args(chain, 1).ok_or_else(err)?;
+            if args.len() != 1 {
+                return Err(err());
+            }
+            if is_var(args[0], depth) {
+                return Ok(num);
+            }
+            chain = lam_body(args[0]).ok_or_else(err)?;
+            num += 1;
+            depth += 1;
+        }
+    }
+
+    let args = var_args(body, 1).ok_or_else(err)?;
+    for arg in args.iter() {
+        match lam_body(arg) {
+            Some(id_body) if is_var(id_body, 1) => {},
+            _ => return Err(err()),
+        };
+    }
+    Ok(-(args.len() as i32))
+}
+
+fn is_list_end(t: &Term) -> bool {
+    match t.kind {
+        Kind::Name(name) => name == "nil" || name == "false",
+        _ => term_to_bool(t) == Ok(false),
+    }
+}
+
+// Elements of a list of chained church pairs, i.e. `λ1 head tail`.
+fn list_elems<'t, 'a>(t: &'t Term<'a>) -> Result<Vec<&'t Term<'a>>, ParseError> {
+    let mut elems = Vec::new();
+    let mut t = t;
+    while !is_list_end(t) {
+        match lam_body(t).and_then(|body| var_args(body, 1)) {
+            Some(ref args) if args.len() == 2 => {
+                elems.push(args[0]);
+                t = args[1];
+            },
+            _ => return Err(invalid(t.at, "Church pair (list element)")),
+        };
+    }
+    Ok(elems)
+}
+
+// A rectangle is `λ1 x y w h`.
+fn term_to_rect(t: &Term,
+                scaling_factor: i32,
+                x_offset: i32,
+                y_offset: i32) -> Result<Rect, ParseError> {
+    let args = match lam_body(t).and_then(|body| var_args(body, 1)) {
+        Some(args) if args.len() == 4 => args,
+        _ => return Err(invalid(t.at, "rectangle (4-tuple)")),
+    };
+    let x = term_to_int(args[0])?;
+    let y = term_to_int(args[1])?;
+    let width = term_to_int(args[2])?;
+    let height = term_to_int(args[3])?;
+
+    scaled_rect(x, y, width, height, scaling_factor, x_offset, y_offset)
+        .ok_or_else(|| invalid(t.at, "rectangle (coordinates overflow when scaled)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_out::tests::Rng;
+
+    #[test]
+    fn de_bruijn_bool() {
+        assert_eq!(parse_church_bool("λλ2"), Ok(true));
+        assert_eq!(parse_church_bool("(\\ \\ 1)"), Ok(false));
+        assert_eq!(parse_church_bool("λ.λ.1"), Ok(false));
+        assert!(parse_church_bool("λλ3").is_err());
+        assert!(parse_church_bool("λ1").is_err());
+    }
+
+    #[test]
+    fn de_bruijn_int() {
+        assert_eq!(clni_to_int("λ1"), Ok(0));
+        assert_eq!(clni_to_int("λλ1 2"), Ok(1));
+        assert_eq!(clni_to_int("(λλ1 (λ1 (λ1 4)))"), Ok(3));
+        assert_eq!(clni_to_int("λ1 (λ1)"), Ok(-1));
+        assert_eq!(clni_to_int("λ1 (λ1) (λ1)"), Ok(-2));
+        assert!(clni_to_int("λλ1 1").is_err());
+        assert!(clni_to_int("λ1 (λ2)").is_err());
+    }
+
+    #[test]
+    fn de_bruijn_rect_list() {
+        // ((1, 0, 2, 1), (0, 0, 1, 1))
+        let s = "λ1 (λ1 (λλ1 2) (λ1) (λλ1 (λ1 3)) (λλ1 2)) (λ1 (λ1 (λ1) (λ1) (λλ1 2) (λλ1 2)) nil)";
+        let expected = vec![Rect::new(0, 10, 1, 1), Rect::new(1, 10, 2, 1)];
+        assert_eq!(parse_rect_list(s, 1, 0, 10), Ok(expected));
+        assert_eq!(parse_rect_list("λλ1", 1, 0, 0), Ok(Vec::new()));
+    }
+
+    // A positive CLNI integer in de Bruijn notation.
+    fn clni(n: usize) -> String {
+        format!("λλ1 {}{}{}", "(λ1 ".repeat(n - 1), n + 1, ")".repeat(n - 1))
+    }
+
+    #[test]
+    fn de_bruijn_string() {
+        assert_eq!(clni_to_int(&clni(65)), Ok(65));
+        let s = format!("λ1 ({}) (λ1 ({}) (λλ1))", clni(72), clni(105));
+        assert_eq!(parse_string(&s), Ok("Hi".to_string()));
+        assert_eq!(parse_int_list(&s), Ok(vec![72, 105]));
+    }
+
+    #[test]
+    fn de_bruijn_random_tokens_dont_panic() {
+        let tokens = ["(", ")", "λ", "\\", ".", " ", "1", "2", "0", "nil", "é", "99999999999"];
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..5000 {
+            let s: String = (0..rng.below(40)).map(|_| rng.pick(&tokens)).collect();
+            let _ = parse_church_bool(&s);
+            let _ = clni_to_int(&s);
+            let _ = parse_string(&s);
+            let _ = parse_int_list(&s);
+            let _ = parse_rect_list(&s, i32::MAX, i32::MAX, 0);
+        }
+    }
+
+    #[test]
+    fn de_bruijn_errors() {
+        let s = "λ1 (λ1 (λ1) (λ1) (λλ1 2)) nil";
+        match parse_rect_list(s, 1, 0, 0) {
+            Err(ParseError::Invalid { offset, .. }) => assert_eq!(offset, s.find('(').unwrap()),
+            other => panic!("expected an Invalid error, got {:?}", other),
+        }
+        assert!(clni_to_int("λ0").is_err());
+        assert!(clni_to_int("(λ1").is_err());
+        assert!(clni_to_int("λ1) x").is_err());
+        assert!(clni_to_int("").is_err());
+        assert!(clni_to_int("λ99999999999999999999999").is_err());
+    }
+
+    #[test]
+    fn de_bruijn_deep_nesting() {
+        let too_deep = |e: Result<i32, ParseError>| match e {
+            Err(ParseError::Invalid { expected, .. }) => expected == "term (nested too deeply)",
+            _ => false,
+        };
+        // spines can be as long as they like.
+        assert_eq!(clni_to_int(&clni(5000)), Ok(5000));
+        assert_eq!(clni_to_int(&format!("λ1{}", " (λ1)".repeat(5000))), Ok(-5000));
+        let list = format!("{}nil{}", "λ1 (λλ1 2) (".repeat(5000), ")".repeat(5000));
+        assert_eq!(parse_int_list(&list), Ok(vec![1; 5000]));
+        let n = 100_000;
+        assert!(!too_deep(clni_to_int(&format!("{}1", "λ".repeat(n)))));
+        assert!(!too_deep(clni_to_int(&format!("{}1{}", "(".repeat(n), ")".repeat(n)))));
+        assert!(!too_deep(clni_to_int(&format!("λ{}", " 1".repeat(n)))));
+
+        // `1 (1 (1) 1) 1` is nested in arguments that aren't the last.
+        let nested = |n| format!("{}1{}", "1 (".repeat(n), ") 1".repeat(n));
+        assert!(!too_deep(clni_to_int(&nested(MAX_DEPTH - 1))));
+        assert!(too_deep(clni_to_int(&nested(MAX_DEPTH))));
+    }
+}
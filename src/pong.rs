@@ -4,8 +4,14 @@
 // implementation.
 //
 
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    str::FromStr,
+};
+
 use sdl2::rect::Rect;
-use crate::game::{UserInput, GameState};
+use crate::game::{self, UserInput, Inputs, Difficulty, Error, GameEvent, GameState};
 
 pub const WINDOW_WIDTH:i32 = 800;
 pub const WINDOW_HEIGHT:i32 = 600;
@@ -19,6 +25,11 @@ const BALL_SIZE_I32:i32 = WINDOW_WIDTH / 200;
 const STEP_SIZE:i32 = 2 * BALL_SIZE_I32;
 const BALL_SIZE:u32 = BALL_SIZE_I32 as u32;
 
+// whoever gets this many points first wins.
+const WIN_SCORE:usize = 10;
+// scores with more digits would run into the paddles.
+pub const MAX_WIN_SCORE:usize = 99;
+
 // player is to the right, CPU is to the left
 const PLAYER_X_CENTER:i32 = 9 * WINDOW_WIDTH / 10;
 const CPU_X_CENTER:i32 = WINDOW_WIDTH / 10;
@@ -29,8 +40,8 @@ pub const PLAYER_X_LEFT:i32 = PLAYER_X_CENTER - BAR_WIDTH / 2;
 pub const CPU_X_LEFT:i32 = CPU_X_CENTER - BAR_WIDTH / 2;
 pub const CPU_X_RIGHT:i32 = CPU_X_CENTER + BAR_WIDTH / 2;
 
-const MIN_Y:i32 = STEP_SIZE;
-const MAX_Y:i32 = WINDOW_HEIGHT - BAR_HEIGHT - STEP_SIZE;
+// the first state of the random numbers for the CPU's error.
+const RNG_SEED:u32 = 0x5eed;
 
 // scores are shown as if in a seven-segment LED panel, according to the scheme:
 //     A
@@ -56,6 +67,145 @@ pub const ACTIVE_LEDS_NUM: &[&[bool]] = &[
 pub const LED_SMALLER_DIM:u32 = CPU_X_CENTER as u32 / 10;
 pub const LED_LARGER_DIM:u32 = 3 * LED_SMALLER_DIM;
 
+// from one digit of a score to the next.
+const LED_DIGIT_PITCH:i32 = (LED_LARGER_DIM + LED_SMALLER_DIM) as i32;
+
+/// The game's parameters that can be changed at runtime, e.g. from a config
+/// file. Defaults to the constants above.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tunables {
+    /// How far a paddle moves per tick, in pixels.
+    pub step_size: i32,
+    pub bar_height: i32,
+    pub ball_size: u32,
+    pub win_score: usize,
+}
+
+impl Default for Tunables {
+    fn default() -> Tunables {
+        Tunables {
+            step_size: STEP_SIZE,
+            bar_height: BAR_HEIGHT,
+            ball_size: BALL_SIZE,
+            win_score: WIN_SCORE,
+        }
+    }
+}
+
+impl Tunables {
+    /// Check that the paddles can still move and fit in the window, and that
+    /// the ball and the scores fit between the paddles. On error, returns
+    /// the offending field's name too.
+    ///
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        let positive = [
+            ("step_size", self.step_size > 0),
+            ("bar_height", self.bar_height > 0),
+            ("ball_size", self.ball_size > 0),
+            ("win_score", self.win_score > 0),
+        ];
+        if let Some((field, _)) = positive.iter().find(|(_, ok)| !ok) {
+            return Err((field, "must be positive".to_string()));
+        }
+        if self.win_score > MAX_WIN_SCORE {
+            return Err(("win_score", format!("can't be more than {}, or the score wouldn't fit",
+                                             MAX_WIN_SCORE)));
+        }
+        if self.bar_height + 2 * self.step_size >= WINDOW_HEIGHT {
+            return Err(("bar_height", format!("plus twice step_size must be less than {}",
+                                              WINDOW_HEIGHT)));
+        }
+        if self.ball_size as i32 >= PLAYER_X_LEFT - CPU_X_RIGHT {
+            return Err(("ball_size", format!("must be less than {}", PLAYER_X_LEFT - CPU_X_RIGHT)));
+        }
+        Ok(())
+    }
+
+    /// The parameters as one line, e.g. for a replay's header.
+    pub fn line(&self) -> String {
+        format!("{} {} {} {}",
+                self.step_size,
+                self.bar_height,
+                self.ball_size,
+                self.win_score)
+    }
+
+    /// Read the parameters back from line, if they're valid.
+    pub fn parse_line(line: &str) -> Option<Tunables> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let tunables = match fields[..] {
+            [step_size, bar_height, ball_size, win_score] => Tunables {
+                step_size: step_size.parse().ok()?,
+                bar_height: bar_height.parse().ok()?,
+                ball_size: ball_size.parse().ok()?,
+                win_score: win_score.parse().ok()?,
+            },
+            _ => return None,
+        };
+        tunables.validate().ok()?;
+        Some(tunables)
+    }
+
+    // where the top of a paddle can be.
+    fn min_y(&self) -> i32 {
+        self.step_size
+    }
+
+    fn max_y(&self) -> i32 {
+        WINDOW_HEIGHT - self.bar_height - self.step_size
+    }
+}
+
+// How the CPU plays at a difficulty.
+#[derive(Debug)]
+struct CpuSkill {
+    // ticks between looking at the ball; in between, the CPU keeps going for
+    // where it last aimed.
+    reaction_delay: u32,
+    // how far the paddle moves per tick, in pixels.
+    speed: i32,
+    // how far, at most, the CPU aims off, in pixels; how far exactly changes
+    // whenever the ball changes course.
+    error: i32,
+    // whether the CPU aims where the ball will reach its paddle, rather than
+    // at the ball.
+    predict: bool,
+}
+
+impl CpuSkill {
+    // Normal is a perfect tracker of the ball, slowed down only by step_size.
+    fn new(difficulty: Difficulty, tunables: &Tunables) -> CpuSkill {
+        let step_size = tunables.step_size;
+        match difficulty {
+            Difficulty::Easy => CpuSkill {
+                reaction_delay: 10,
+                speed: (step_size / 2).max(1),
+                error: tunables.bar_height * 2 / 3,
+                predict: false,
+            },
+            Difficulty::Normal => CpuSkill {
+                reaction_delay: 0,
+                speed: step_size,
+                error: 0,
+                predict: false,
+            },
+            Difficulty::Hard => CpuSkill {
+                reaction_delay: 0,
+                speed: step_size,
+                error: tunables.bar_height / 4,
+                predict: true,
+            },
+            Difficulty::Insane => CpuSkill {
+                reaction_delay: 0,
+                speed: 2 * step_size,
+                error: 0,
+                predict: true,
+            },
+        }
+    }
+}
+
 // For simplicity, reflections always happen at a straight angle, so
 // these are the only possible directions.
 //
@@ -67,6 +217,21 @@ enum Direction {
     SE,
 }
 
+impl FromStr for Direction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Direction, ()> {
+        match s {
+            "NE" => Ok(Direction::NE),
+            "NW" => Ok(Direction::NW),
+            "SW" => Ok(Direction::SW),
+            "SE" => Ok(Direction::SE),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct State {
     player_rect: Rect,
     cpu_rect: Rect,
@@ -75,18 +240,35 @@ pub struct State {
     cpu_score: usize,
     player_led_coords: Vec<Rect>,
     cpu_led_coords: Vec<Rect>,
+    // whether the left paddle is controlled by a second player, not the CPU.
+    two_players: bool,
+    paused: bool,
+    quit_requested: bool,
+    // what happened during the last update.
+    events: Vec<GameEvent>,
+    tunables: Tunables,
+    difficulty: Difficulty,
+    cpu_skill: CpuSkill,
+    // where the CPU means to put its paddle's center, and in how many ticks
+    // it looks at the ball again.
+    cpu_target_y: i32,
+    cpu_reaction: u32,
+    cpu_error: i32,
+    // for the CPU's error; always starts the same, so that replays work.
+    rng: u32,
 }
 
 impl State {
-    pub fn new() -> State {
+    pub fn new(two_players: bool, tunables: Tunables, difficulty: Difficulty) -> State {
+        let bar_height = tunables.bar_height;
         let player_rect = Rect::new(PLAYER_X_LEFT,
-                                    WINDOW_HEIGHT / 2 - BAR_HEIGHT / 2,
+                                    WINDOW_HEIGHT / 2 - bar_height / 2,
                                     BAR_WIDTH as u32,
-                                    BAR_HEIGHT as u32);
+                                    bar_height as u32);
         let cpu_rect = Rect::new(CPU_X_LEFT,
-                                 WINDOW_HEIGHT / 2 - BAR_HEIGHT / 2,
+                                 WINDOW_HEIGHT / 2 - bar_height / 2,
                                  BAR_WIDTH as u32,
-                                 BAR_HEIGHT as u32);
+                                 bar_height as u32);
         // These coordinates are relative to the display's upper-left corner,
         // meaning all rectangles must be transposed to the appropriate (x, y)
         // position for both player and CPU scores.
@@ -133,81 +315,284 @@ impl State {
         State {
             player_rect,
             cpu_rect,
-            ball: Ball::new(),
+            ball: Ball::new(tunables.ball_size),
             player_score: 0,
             cpu_score: 0,
             player_led_coords,
             cpu_led_coords,
+            two_players,
+            paused: false,
+            quit_requested: false,
+            events: Vec::new(),
+            tunables,
+            difficulty,
+            cpu_skill: CpuSkill::new(difficulty, &tunables),
+            cpu_target_y: WINDOW_HEIGHT / 2,
+            cpu_reaction: 0,
+            cpu_error: 0,
+            rng: RNG_SEED,
         }
     }
 
     fn update_cpu_pos(&mut self) {
+        if self.cpu_reaction == 0 {
+            self.cpu_target_y = self.cpu_aim() + self.cpu_error;
+            self.cpu_reaction = self.cpu_skill.reaction_delay;
+        } else {
+            self.cpu_reaction -= 1;
+        }
+
         let y = self.cpu_rect.y();
         let cpu_center_y = self.cpu_rect.center().y();
-        let ball_center_y = self.ball.rect.center().y();
-
-        // A very simple AI that simply adjusts its center to the ball's center.
-        if cpu_center_y > ball_center_y && y > MIN_Y {
-            self.cpu_rect.set_y(y - STEP_SIZE);
-        } else if cpu_center_y < ball_center_y && y <= MAX_Y {
-            self.cpu_rect.set_y(y + STEP_SIZE);
+        let tunables = &self.tunables;
+        let speed = self.cpu_skill.speed;
+
+        // A very simple AI that simply adjusts its center to its target.
+        if cpu_center_y > self.cpu_target_y && y > tunables.min_y() {
+            self.cpu_rect.set_y((y - speed).max(0));
+        } else if cpu_center_y < self.cpu_target_y && y <= tunables.max_y() {
+            self.cpu_rect.set_y((y + speed).min(WINDOW_HEIGHT - tunables.bar_height));
         }
         // else, do nothing.
     }
 
-    fn move_player_up(&mut self) {
-        let y = self.player_rect.y();
-        if y > MIN_Y {
-            self.player_rect.set_y(y - STEP_SIZE);
+    // Where the CPU would put its paddle's center, if it didn't err: at the
+    // ball, or, when predicting, where the ball will reach the paddle, and
+    // back at the middle while the ball goes away.
+    //
+    fn cpu_aim(&self) -> i32 {
+        let ball = &self.ball.rect;
+        if !self.cpu_skill.predict {
+            return ball.center().y();
         }
+        // the ball moves as much vertically as it does horizontally.
+        let distance = ball.x() - CPU_X_RIGHT;
+        let y = match self.ball.dir {
+            Direction::NW => ball.y() - distance,
+            Direction::SW => ball.y() + distance,
+            Direction::NE | Direction::SE => return WINDOW_HEIGHT / 2,
+        };
+        // unfold the bounces off the top and bottom.
+        let max_y = WINDOW_HEIGHT - ball.height() as i32;
+        let y = y.rem_euclid(2 * max_y);
+        let y = if y > max_y { 2 * max_y - y } else { y };
+        y + ball.height() as i32 / 2
     }
 
-    fn move_player_down(&mut self) {
-        let y = self.player_rect.y();
-        if y <= MAX_Y {
-            self.player_rect.set_y(y + STEP_SIZE);
+    // Pick how far off the CPU aims next, up to its skill's error.
+    fn roll_cpu_error(&mut self) {
+        let error = self.cpu_skill.error;
+        if error == 0 {
+            return;
         }
+        // a linear congruential generator, with glibc's constants.
+        self.rng = self.rng.wrapping_mul(1103515245).wrapping_add(12345);
+        self.cpu_error = ((self.rng >> 16) % (2 * error as u32 + 1)) as i32 - error;
+    }
+}
+
+// move either player's paddle.
+fn move_paddle(rect: &mut Rect, input: UserInput, tunables: &Tunables) {
+    let y = rect.y();
+    match input {
+        UserInput::Up if y > tunables.min_y() => rect.set_y(y - tunables.step_size),
+        UserInput::Down if y <= tunables.max_y() => rect.set_y(y + tunables.step_size),
+        _ => {},
     }
 }
 
 impl GameState for State {
-    fn game_over(&mut self) -> bool {
-        return self.player_score >= 10 || self.cpu_score >= 10;
+    fn game_over(&mut self) -> Result<bool, Error> {
+        let win_score = self.tunables.win_score;
+        return Ok(self.quit_requested || self.player_score >= win_score || self.cpu_score >= win_score);
     }
 
-    fn update(&mut self, input: UserInput) {
-        match input {
-            UserInput::Up => self.move_player_up(),
-            UserInput::Down => self.move_player_down(),
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+        self.events.clear();
+        match inputs.player {
+            UserInput::Pause => self.paused = !self.paused,
+            UserInput::Restart => *self = State::new(self.two_players, self.tunables, self.difficulty),
+            UserInput::Quit => self.quit_requested = true,
             _ => {},
         }
+        if self.paused {
+            return Ok(());
+        }
+
+        // the ball is served as soon as someone scores, so serving does
+        // nothing.
+        move_paddle(&mut self.player_rect, inputs.player, &self.tunables);
+        if self.two_players {
+            move_paddle(&mut self.cpu_rect, inputs.second_player, &self.tunables);
+        }
 
         self.ball.update_pos(&self.player_rect,
                              &self.cpu_rect,
                              &mut self.player_score,
-                             &mut self.cpu_score);
-        self.update_cpu_pos();
+                             &mut self.cpu_score,
+                             &mut self.events);
+        if !self.two_players {
+            let new_course = self.events.iter()
+                .any(|event| *event == GameEvent::PaddleHit || *event == GameEvent::Score);
+            if new_course {
+                self.roll_cpu_error();
+            }
+            self.update_cpu_pos();
+        }
+        Ok(())
     }
 
-    fn get_rects(&mut self) -> Vec<Rect> {
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
         let mut rects = vec![self.cpu_rect.clone(), self.player_rect.clone(), self.ball.rect.clone(), ];
-        append_active_led_rects(&mut rects, self.player_score, &self.player_led_coords);
-        append_active_led_rects(&mut rects, self.cpu_score, &self.cpu_led_coords);
+        // the player's score grows to the left, away from the window's edge.
+        append_active_led_rects(&mut rects, self.player_score, &self.player_led_coords, -1);
+        append_active_led_rects(&mut rects, self.cpu_score, &self.cpu_led_coords, 1);
+
+        Ok(rects)
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        if self.paused {
+            Ok(Some("PAUSED".to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        Ok(Some(game::result_text(self.cpu_score, self.player_score, self.two_players)))
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        Ok(Some((self.cpu_score, self.player_score)))
+    }
+
+    fn dump_state(&mut self) -> Option<String> {
+        Some(format!("{:#?}\n", self))
+    }
+
+    // A field per line; the parameters are saved too, since the positions
+    // only make sense with them. Everything else is set up by new.
+    fn save_state(&mut self) -> Option<String> {
+        let tunables = &self.tunables;
+        let mut saved = String::new();
+        let _ = write!(saved,
+                       "step_size {}\nbar_height {}\nball_size {}\nwin_score {}\n",
+                       tunables.step_size,
+                       tunables.bar_height,
+                       tunables.ball_size,
+                       tunables.win_score);
+        let _ = write!(saved,
+                       "player_y {}\ncpu_y {}\nplayer_score {}\ncpu_score {}\npaused {}\n",
+                       self.player_rect.y(),
+                       self.cpu_rect.y(),
+                       self.player_score,
+                       self.cpu_score,
+                       self.paused);
+        let _ = write!(saved,
+                       "cpu_target_y {}\ncpu_reaction {}\ncpu_error {}\nrng {}\n",
+                       self.cpu_target_y,
+                       self.cpu_reaction,
+                       self.cpu_error,
+                       self.rng);
+        self.ball.save(&mut saved);
+        Some(saved)
+    }
+
+    fn load_state(&mut self, saved: &str) -> Result<(), String> {
+        let fields = Fields::parse(saved)?;
+        let tunables = Tunables {
+            step_size: fields.get("step_size")?,
+            bar_height: fields.get("bar_height")?,
+            ball_size: fields.get("ball_size")?,
+            win_score: fields.get("win_score")?,
+        };
+        if tunables != self.tunables {
+            return Err("the game was saved with other [native] parameters".to_string());
+        }
+        // the paddles stay on the screen, like the ball, and the game is over
+        // once a score reaches win_score.
+        let paddle_y = |key: &str| match fields.get(key)? {
+            y if (0..=WINDOW_HEIGHT - tunables.bar_height).contains(&y) => Ok(y),
+            y => Err(format!("the paddle at {} {} is off the screen", key, y)),
+        };
+        let score = |key: &str| match fields.get(key)? {
+            score if score <= tunables.win_score => Ok(score),
+            score => Err(format!("{} {} is more than win_score", key, score)),
+        };
+        let mut state = State::new(self.two_players, self.tunables, self.difficulty);
+        state.player_rect.set_y(paddle_y("player_y")?);
+        state.cpu_rect.set_y(paddle_y("cpu_y")?);
+        state.player_score = score("player_score")?;
+        state.cpu_score = score("cpu_score")?;
+        state.paused = fields.get("paused")?;
+        state.cpu_target_y = fields.get("cpu_target_y")?;
+        state.cpu_reaction = fields.get("cpu_reaction")?;
+        state.cpu_error = fields.get("cpu_error")?;
+        state.rng = fields.get("rng")?;
+        state.ball.load(&fields)?;
+        *self = state;
+        Ok(())
+    }
+}
+
+// The fields of a saved state, by name.
+struct Fields<'a>(HashMap<&'a str, &'a str>);
+
+impl<'a> Fields<'a> {
+    fn parse(saved: &'a str) -> Result<Fields<'a>, String> {
+        let mut fields = HashMap::new();
+        for line in saved.lines().filter(|line| !line.trim().is_empty()) {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next(), words.next()) {
+                (Some(key), Some(value), None) => {
+                    if fields.insert(key, value).is_some() {
+                        return Err(format!("'{}' is given twice", key));
+                    }
+                },
+                _ => return Err(format!("expected a name and a value, not '{}'", line.trim())),
+            }
+        }
+        Ok(Fields(fields))
+    }
 
-        rects
+    fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        match self.0.get(key) {
+            None => Err(format!("'{}' is missing", key)),
+            Some(value) => value.parse().map_err(|_| format!("invalid {} '{}'", key, value)),
+        }
     }
 }
 
+// Scores with several digits are drawn digit by digit, starting from the
+// one at led_coords and going in the given direction: 1 to the right, -1 to
+// the left.
+//
 fn append_active_led_rects(rects: &mut Vec<Rect>,
                            score: usize,
-                           led_coords: &Vec<Rect>) {
-    for (i, active_led) in ACTIVE_LEDS_NUM[score].iter().enumerate() {
-        if *active_led {
-            rects.push(led_coords[i].clone());
+                           led_coords: &Vec<Rect>,
+                           direction: i32) {
+    let mut digits: Vec<usize> = score.to_string().bytes().map(|d| (d - b'0') as usize).collect();
+    if direction < 0 {
+        digits.reverse();
+    }
+    for (n, digit) in digits.into_iter().enumerate() {
+        let offset = n as i32 * LED_DIGIT_PITCH * direction;
+        for (i, active_led) in ACTIVE_LEDS_NUM[digit].iter().enumerate() {
+            if *active_led {
+                let mut rect = led_coords[i].clone();
+                rect.set_x(rect.x() + offset);
+                rects.push(rect);
+            }
         }
     }
 }
 
+#[derive(Debug)]
 struct Ball {
     dir: Direction,
     accel: i32,
@@ -215,12 +600,12 @@ struct Ball {
 }
 
 impl Ball {
-    pub fn new() -> Ball {
+    pub fn new(size: u32) -> Ball {
         Ball {
             dir: Direction::SE,
             accel: 1,
             rect: Rect::new(WINDOW_WIDTH / 2, WINDOW_HEIGHT / 2,
-                            BALL_SIZE, BALL_SIZE),
+                            size, size),
         }
     }
 
@@ -231,9 +616,39 @@ impl Ball {
         self.rect.set_y(WINDOW_HEIGHT / 2);
     }
 
+    fn save(&self, saved: &mut String) {
+        let _ = write!(saved,
+                       "ball_x {}\nball_y {}\nball_dir {:?}\nball_accel {}\n",
+                       self.rect.x(),
+                       self.rect.y(),
+                       self.dir,
+                       self.accel);
+    }
+
+    // The ball must be on the screen and moving, or it could end up going
+    // through a wall. A step as wide as the window would overflow the
+    // position arithmetic long before it makes a playable game.
+    fn load(&mut self, fields: &Fields) -> Result<(), String> {
+        let x = fields.get("ball_x")?;
+        let y = fields.get("ball_y")?;
+        let accel = fields.get("ball_accel")?;
+        if !(0..=WINDOW_WIDTH).contains(&x) || !(0..=WINDOW_HEIGHT - self.rect.height() as i32).contains(&y) {
+            return Err(format!("the ball at ({}, {}) is off the screen", x, y));
+        }
+        if !(1..WINDOW_WIDTH).contains(&accel) {
+            return Err(format!("invalid ball_accel '{}'", accel));
+        }
+        self.rect.set_x(x);
+        self.rect.set_y(y);
+        self.dir = fields.get("ball_dir")?;
+        self.accel = accel;
+        Ok(())
+    }
+
     pub fn update_pos(&mut self,
                       player_rect: &Rect, cpu_rect: &Rect,
-                      player_score: &mut usize, cpu_score: &mut usize) {
+                      player_score: &mut usize, cpu_score: &mut usize,
+                      events: &mut Vec<GameEvent>) {
         let x = self.rect.x();
         let y = self.rect.y();
         let (mut new_x, mut new_y) = self.get_new_pos(x, y);
@@ -242,16 +657,25 @@ impl Ball {
         if new_x < 0 {
             *player_score += 1;
             self.reset();
+            events.push(GameEvent::Score);
             return;
         } else if new_x > WINDOW_WIDTH {
             *cpu_score += 1;
             self.reset();
+            events.push(GameEvent::Score);
             return;
         }
 
-        if self.reflect_upper_or_lower_bound(new_y)
-            || self.reflect_hit_bar(new_x, new_y, player_rect, cpu_rect)
-        {
+        let reflected = if self.reflect_upper_or_lower_bound(new_y) {
+            events.push(GameEvent::WallBounce);
+            true
+        } else if self.reflect_hit_bar(new_x, new_y, player_rect, cpu_rect) {
+            events.push(GameEvent::PaddleHit);
+            true
+        } else {
+            false
+        };
+        if reflected {
             // Rust doesn't allow doing this directly
             let (temp1, temp2) = self.get_new_pos(x, y);
             new_x = temp1;
@@ -326,3 +750,121 @@ impl Ball {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_digit_scores() {
+        let mut state = State::new(false, Tunables::default(), Difficulty::Normal);
+        state.player_score = 10;
+        state.cpu_score = 10;
+        let rects = state.get_rects().unwrap();
+        // paddles and ball, then a 1 and a 0 for each score.
+        assert_eq!(rects.len(), 3 + 2 * (2 + 6));
+        // the player's 0 stays at the window's edge, and the 1 is left of it.
+        let player_b = state.player_led_coords[1];
+        assert!(rects.contains(&player_b));
+        assert!(rects.contains(&Rect::new(player_b.x() - LED_DIGIT_PITCH, player_b.y(),
+                                          player_b.width(), player_b.height())));
+        // the CPU's 1 stays at the edge, and the 0 is right of it.
+        let cpu_a = state.cpu_led_coords[0];
+        assert!(!rects.contains(&cpu_a));
+        assert!(rects.contains(&Rect::new(cpu_a.x() + LED_DIGIT_PITCH, cpu_a.y(),
+                                          cpu_a.width(), cpu_a.height())));
+    }
+
+    #[test]
+    fn tunables() {
+        let tunables = Tunables {
+            win_score: 3,
+            bar_height: 100,
+            ..Tunables::default()
+        };
+        assert_eq!(tunables.validate(), Ok(()));
+        let mut state = State::new(false, tunables, Difficulty::Normal);
+        assert_eq!(state.player_rect.height(), 100);
+        state.player_score = 2;
+        assert!(!state.game_over().unwrap());
+        state.player_score = 3;
+        assert!(state.game_over().unwrap());
+
+        assert!(Tunables { win_score: 100, ..tunables }.validate().is_err());
+        assert!(Tunables { step_size: 0, ..tunables }.validate().is_err());
+        assert!(Tunables { bar_height: WINDOW_HEIGHT, ..tunables }.validate().is_err());
+    }
+
+    fn ball_towards_cpu(state: &mut State, x: i32, y: i32, dir: Direction) {
+        state.ball.rect.set_x(x);
+        state.ball.rect.set_y(y);
+        state.ball.dir = dir;
+    }
+
+    #[test]
+    fn prediction() {
+        let mut state = State::new(false, Tunables::default(), Difficulty::Hard);
+        let ball_size = state.ball.rect.height() as i32;
+        // straight to the paddle.
+        ball_towards_cpu(&mut state, CPU_X_RIGHT + 100, 300, Direction::NW);
+        assert_eq!(state.cpu_aim(), 200 + ball_size / 2);
+        // off the bottom first.
+        ball_towards_cpu(&mut state, CPU_X_RIGHT + 100, 550, Direction::SW);
+        let max_y = WINDOW_HEIGHT - ball_size;
+        assert_eq!(state.cpu_aim(), 2 * max_y - 650 + ball_size / 2);
+        // going away: back to the middle.
+        state.ball.dir = Direction::NE;
+        assert_eq!(state.cpu_aim(), WINDOW_HEIGHT / 2);
+
+        // without prediction, the CPU just follows the ball.
+        let mut state = State::new(false, Tunables::default(), Difficulty::Normal);
+        ball_towards_cpu(&mut state, CPU_X_RIGHT + 100, 300, Direction::NW);
+        assert_eq!(state.cpu_aim(), state.ball.rect.center().y());
+    }
+
+    #[test]
+    fn difficulty_changes_speed_and_reaction() {
+        let nothing = Inputs { player: UserInput::Nothing, second_player: UserInput::Nothing };
+        let step_size = Tunables::default().step_size;
+        let moved = |difficulty: Difficulty, ticks: usize| {
+            let mut state = State::new(false, Tunables::default(), difficulty);
+            let start = state.cpu_rect.y();
+            ball_towards_cpu(&mut state, CPU_X_RIGHT, 0, Direction::NW);
+            // the ball stays put, right above the paddle.
+            state.ball.accel = 0;
+            for _ in 0..ticks {
+                state.update(nothing).unwrap();
+            }
+            start - state.cpu_rect.y()
+        };
+        assert_eq!(moved(Difficulty::Normal, 3), 3 * step_size);
+        assert_eq!(moved(Difficulty::Insane, 3), 6 * step_size);
+        assert_eq!(moved(Difficulty::Easy, 3), 3 * step_size / 2);
+
+        // easy only looks at the ball every so often.
+        let mut state = State::new(false, Tunables::default(), Difficulty::Easy);
+        state.ball.accel = 0;
+        state.update(nothing).unwrap();
+        let target = state.cpu_target_y;
+        state.ball.rect.set_y(0);
+        for _ in 0..state.cpu_skill.reaction_delay {
+            state.update(nothing).unwrap();
+            assert_eq!(state.cpu_target_y, target);
+        }
+        state.update(nothing).unwrap();
+        assert_ne!(state.cpu_target_y, target);
+    }
+
+    #[test]
+    fn cpu_error_is_bounded_and_repeatable() {
+        let mut first = State::new(false, Tunables::default(), Difficulty::Easy);
+        let mut second = State::new(false, Tunables::default(), Difficulty::Easy);
+        let error = first.cpu_skill.error;
+        for _ in 0..100 {
+            first.roll_cpu_error();
+            second.roll_cpu_error();
+            assert!(first.cpu_error.abs() <= error);
+            assert_eq!(first.cpu_error, second.cpu_error);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// raster.rs: draws frames in memory, without SDL, and saves them as images.
+//
+// The game only ever draws rectangles of one color on a background of
+// another, so that's all there is to rasterize. Images are written either as
+// binary PPM or as PNG; the PNG encoder doesn't compress, which keeps it short
+// and is fine for frames that are converted to something else anyway.
+//
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    config::Colors,
+    font,
+    game::{Frame, Renderer, OVERLAY_MARGIN, OVERLAY_SCALE, TEXT_SCALE},
+    pong::{WINDOW_WIDTH, WINDOW_HEIGHT},
+};
+
+/// An RGB image, 3 bytes per pixel, row by row from the top.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 3],
+        }
+    }
+
+    #[cfg(test)]
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    pub fn clear(&mut self, color: [u8; 3]) {
+        for pixel in self.pixels.chunks_mut(3) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    /// Fill the rectangle, or whatever part of it is inside the image.
+    pub fn fill_rect(&mut self, rect: Rect, color: [u8; 3]) {
+        let clamp_x = |x: i64| x.max(0).min(self.width as i64) as usize;
+        let clamp_y = |y: i64| y.max(0).min(self.height as i64) as usize;
+        let (left, right) = (clamp_x(rect.x() as i64), clamp_x(rect.x() as i64 + rect.width() as i64));
+        let (top, bottom) = (clamp_y(rect.y() as i64), clamp_y(rect.y() as i64 + rect.height() as i64));
+
+        let row_len = self.width as usize * 3;
+        for row in self.pixels.chunks_mut(row_len).take(bottom).skip(top) {
+            for pixel in row[left * 3..right * 3].chunks_mut(3) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Draw a frame the same way the game's window does.
+    pub fn draw_frame(&mut self, frame: &Frame, colors: &Colors) {
+        let rects = frame.rects;
+        self.clear(colors.background);
+        for rect in rects {
+            self.fill_rect(*rect, colors.foreground);
+        }
+        if let Some(text) = frame.text {
+            let text_rects = font::centered_text_rects(text,
+                                                       WINDOW_WIDTH / 2,
+                                                       WINDOW_HEIGHT / 2,
+                                                       TEXT_SCALE);
+            for rect in text_rects {
+                self.fill_rect(rect, colors.foreground);
+            }
+        }
+        if let Some(overlay) = frame.overlay {
+            for rect in font::line_rects(overlay, OVERLAY_MARGIN, OVERLAY_MARGIN, OVERLAY_SCALE) {
+                self.fill_rect(rect, colors.foreground);
+            }
+        }
+    }
+
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.pixels)
+    }
+
+    pub fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGB, no interlacing.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_png_chunk(out, b"IHDR", &header)?;
+
+        // each row starts with its filter type, which is always none.
+        let row_len = self.width as usize * 3;
+        let mut raw = Vec::with_capacity((row_len + 1) * self.height as usize);
+        for row in self.pixels.chunks(row_len) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        write_png_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+        write_png_chunk(out, b"IEND", &[])
+    }
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+// A zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK:usize = 0xffff;
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32<'a, I: IntoIterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD:u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// Saves every nth tick's frame to a directory, as frame_000001.png, etc.,
+/// numbered by tick.
+///
+pub struct FrameExport {
+    dir: PathBuf,
+    every: u64,
+    format: ImageFormat,
+    colors: Colors,
+    framebuffer: Framebuffer,
+}
+
+impl FrameExport {
+    pub fn new(dir: PathBuf, every: u64, format: ImageFormat, colors: Colors) -> FrameExport {
+        FrameExport {
+            dir,
+            every: every.max(1),
+            format,
+            colors,
+            framebuffer: Framebuffer::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn save(&self, tick: u64) -> io::Result<()> {
+        let path = self.dir.join(format!("frame_{:06}.{}", tick, self.format.extension()));
+        let mut out = BufWriter::new(File::create(path)?);
+        match self.format {
+            ImageFormat::Ppm => self.framebuffer.write_ppm(&mut out)?,
+            ImageFormat::Png => self.framebuffer.write_png(&mut out)?,
+        }
+        out.flush()
+    }
+}
+
+impl Renderer for FrameExport {
+    /// Save the frame, if it's one of the ticks to be saved.
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        if !frame.tick.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        self.framebuffer.draw_frame(frame, &self.colors);
+        self.save(frame.tick).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK:[u8; 3] = [0, 0, 0];
+    const WHITE:[u8; 3] = [0xff, 0xff, 0xff];
+
+    #[test]
+    fn rects_are_clipped() {
+        let mut fb = Framebuffer::new(4, 3);
+        fb.fill_rect(Rect::new(-2, 1, 4, 10), WHITE);
+        assert_eq!(fb.pixel(0, 0), BLACK);
+        assert_eq!(fb.pixel(1, 1), WHITE);
+        assert_eq!(fb.pixel(1, 2), WHITE);
+        assert_eq!(fb.pixel(2, 1), BLACK);
+
+        fb.fill_rect(Rect::new(10, 10, 5, 5), WHITE);
+        fb.fill_rect(Rect::new(3, 0, 100, 1), WHITE);
+        assert_eq!(fb.pixel(3, 0), WHITE);
+        assert_eq!(fb.pixel(2, 0), BLACK);
+    }
+
+    #[test]
+    fn ppm_output() {
+        let mut fb = Framebuffer::new(2, 1);
+        fb.fill_rect(Rect::new(1, 0, 1, 1), WHITE);
+        let mut out = Vec::new();
+        fb.write_ppm(&mut out).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\0\0\0\xff\xff\xff");
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn png_output() {
+        let fb = Framebuffer::new(3, 2);
+        let mut out = Vec::new();
+        fb.write_png(&mut out).unwrap();
+        assert!(out.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x03\0\0\0\x02"));
+        assert!(out.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+    }
+
+    #[test]
+    fn zlib_blocks() {
+        let data = vec![7; 0x10000];
+        let stream = zlib_stored(&data);
+        // a full block, then a final one with the remaining byte.
+        assert_eq!(&stream[2..7], &[0, 0xff, 0xff, 0, 0]);
+        assert_eq!(&stream[7 + 0xffff..7 + 0xffff + 5], &[1, 1, 0, 0xfe, 0xff]);
+        assert_eq!(stream.len(), 2 + 5 + 0xffff + 5 + 1 + 4);
+    }
+}
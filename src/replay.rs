@@ -0,0 +1,462 @@
+// replay.rs: recording a session's inputs and playing them back.
+//
+// Both backends are deterministic, so the inputs given to each tick are all
+// that's needed to reproduce a session exactly. A replay file is a short
+// header followed by the inputs, in the same format as headless scripts:
+//
+//     lambda-pong replay 1
+//     backend lambda 9f3c0a61d2e4b857
+//     players 1
+//     difficulty normal
+//     rules -
+//     60 nothing
+//     12 up
+//     ...
+//
+// where the number after "lambda" is a hash of the lambda source file, so
+// that playing a replay with a different source can be detected, and the
+// rules are the native backend's parameters, as in the [native] section of
+// the config, or "-" for the lambda backend.
+//
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    game::{Difficulty, Error, GameEvent, GameState, Inputs, UserInput},
+    headless::Script,
+    pong::Tunables,
+};
+
+const MAGIC:&str = "lambda-pong replay";
+pub const VERSION:u32 = 1;
+
+/// What a replay was recorded with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Native,
+    /// The lambda backend, with a hash of its source file.
+    Lambda(u64),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::Native => write!(f, "native"),
+            Source::Lambda(hash) => write!(f, "lambda {:016x}", hash),
+        }
+    }
+}
+
+impl Source {
+    pub fn parse(s: &str) -> Option<Source> {
+        let mut words = s.split_whitespace();
+        let source = match (words.next(), words.next()) {
+            (Some("native"), None) => Source::Native,
+            (Some("lambda"), Some(hash)) => Source::Lambda(u64::from_str_radix(hash, 16).ok()?),
+            _ => return None,
+        };
+        match words.next() {
+            None => Some(source),
+            Some(_) => None,
+        }
+    }
+
+    /// The lambda backend with the given source file.
+    pub fn lambda_file(filename: &str) -> io::Result<Source> {
+        fs::read(filename).map(|bytes| Source::Lambda(hash(&bytes)))
+    }
+}
+
+// 64-bit FNV-1a; only meant to tell files apart, not to be secure.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    pub source: Source,
+    pub two_players: bool,
+    pub difficulty: Difficulty,
+    /// The native backend's parameters; None for the lambda backend, and
+    /// for files from before they were written.
+    pub rules: Option<Tunables>,
+}
+
+impl Header {
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{} {}", MAGIC, VERSION)?;
+        self.write_fields(out)
+    }
+
+    /// Write the fields, one per line, after a file's first line.
+    pub fn write_fields<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "backend {}", self.source)?;
+        writeln!(out, "players {}", if self.two_players { 2 } else { 1 })?;
+        writeln!(out, "difficulty {}", self.difficulty.name())?;
+        match self.rules {
+            Some(ref rules) => writeln!(out, "rules {}", rules.line()),
+            None => writeln!(out, "rules -"),
+        }
+    }
+
+    /// Read the fields written by write_fields, from the next lines.
+    pub fn parse_fields<'a, I>(lines: &mut I) -> Result<Header, String>
+        where I: Iterator<Item = (usize, &'a str)>
+    {
+        let source = header_value(lines, "backend ")
+            .and_then(|s| Source::parse(s).ok_or(format!("invalid backend '{}'", s)))?;
+        let two_players = match header_value(lines, "players ")? {
+            "1" => false,
+            "2" => true,
+            players => return Err(format!("invalid number of players '{}'", players)),
+        };
+        let difficulty = header_value(lines, "difficulty ")
+            .and_then(|s| Difficulty::from_name(s).ok_or(format!("invalid difficulty '{}'", s)))?;
+        let rules = match header_value(lines, "rules ")? {
+            "-" => None,
+            rules => Some(Tunables::parse_line(rules).ok_or(format!("invalid rules '{}'", rules))?),
+        };
+        Ok(Header { source, two_players, difficulty, rules })
+    }
+}
+
+/// The rest of the next line, which must start with key.
+pub fn header_value<'a, I>(lines: &mut I, key: &str) -> Result<&'a str, String>
+    where I: Iterator<Item = (usize, &'a str)>
+{
+    match lines.next() {
+        Some((_, line)) if line.starts_with(key) => Ok(line[key.len()..].trim()),
+        _ => Err(format!("expected '{}' in the header", key)),
+    }
+}
+
+pub struct Replay {
+    pub header: Header,
+    pub script: Script,
+}
+
+impl Replay {
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().enumerate();
+        let version = header_value(&mut lines, MAGIC)
+            .map_err(|_| "not a replay file".to_string())?;
+        if version.parse() != Ok(VERSION) {
+            return Err(format!("unsupported replay version '{}'", version));
+        }
+        let header = Header::parse_fields(&mut lines)?;
+
+        let script = Script::parse_lines(lines)?;
+        Ok(Replay { header, script })
+    }
+
+    pub fn read(filename: &str) -> Result<Replay, String> {
+        fs::read_to_string(filename)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Replay::parse(&text))
+    }
+}
+
+/// Records every tick's inputs to a replay file, as they're given to the
+/// state.
+///
+/// The file is finished when the recording is dropped, so that it's still
+/// written if the game panics, which is when it's most useful.
+///
+pub struct Recording<S: GameState, W: Write = BufWriter<File>> {
+    state: S,
+    out: W,
+    // the inputs being repeated, and for how many ticks so far.
+    run: Option<(Inputs, u64)>,
+}
+
+impl<S: GameState> Recording<S> {
+    pub fn create(state: S, filename: &str, header: Header) -> io::Result<Recording<S>> {
+        Recording::new(state, BufWriter::new(File::create(filename)?), header)
+    }
+}
+
+impl<S: GameState, W: Write> Recording<S, W> {
+    pub fn new(state: S, mut out: W, header: Header) -> io::Result<Recording<S, W>> {
+        header.write(&mut out)?;
+        Ok(Recording {
+            state,
+            out,
+            run: None,
+        })
+    }
+
+    fn record(&mut self, inputs: Inputs) -> io::Result<()> {
+        match self.run {
+            Some((ref run_inputs, ref mut count)) if *run_inputs == inputs => {
+                *count += 1;
+                Ok(())
+            },
+            _ => {
+                let finished = self.run.replace((inputs, 1));
+                self.write_run(finished)
+            },
+        }
+    }
+
+    fn write_run(&mut self, run: Option<(Inputs, u64)>) -> io::Result<()> {
+        match run {
+            Some((inputs, count)) => writeln!(self.out,
+                                              "{} {} {}",
+                                              count,
+                                              inputs.player.name(),
+                                              inputs.second_player.name()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: GameState, W: Write> Drop for Recording<S, W> {
+    fn drop(&mut self) {
+        let run = self.run.take();
+        if let Err(e) = self.write_run(run).and_then(|_| self.out.flush()) {
+            eprintln!("failed to write replay: {}", e);
+        }
+    }
+}
+
+impl<S: GameState, W: Write> GameState for Recording<S, W> {
+    fn game_over(&mut self) -> Result<bool, Error> {
+        self.state.game_over()
+    }
+
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+        if let Err(e) = self.record(inputs) {
+            eprintln!("failed to write replay: {}", e);
+        }
+        self.state.update(inputs)
+    }
+
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
+        self.state.get_rects()
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        self.state.get_text()
+    }
+
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        self.state.result()
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        self.state.events()
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        self.state.scores()
+    }
+
+    fn dump_state(&mut self) -> Option<String> {
+        self.state.dump_state()
+    }
+
+    fn save_state(&mut self) -> Option<String> {
+        self.state.save_state()
+    }
+
+    // replays always start from the beginning.
+    fn load_state(&mut self, _saved: &str) -> Result<(), String> {
+        Err("a game can't be loaded while it's being recorded".to_string())
+    }
+}
+
+/// Plays a replay's inputs back, ignoring the user's, except for pausing
+/// the playback and quitting. Once the replay is over, the user takes over.
+///
+pub struct Replaying<S: GameState> {
+    state: S,
+    script: Script,
+    paused: bool,
+}
+
+impl<S: GameState> Replaying<S> {
+    pub fn new(state: S, script: Script) -> Replaying<S> {
+        Replaying {
+            state,
+            script,
+            paused: false,
+        }
+    }
+}
+
+impl<S: GameState> GameState for Replaying<S> {
+    fn game_over(&mut self) -> Result<bool, Error> {
+        self.state.game_over()
+    }
+
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+        match inputs.player {
+            UserInput::Quit => return self.state.update(inputs),
+            UserInput::Pause => self.paused = !self.paused,
+            _ => {},
+        }
+        if self.paused {
+            return Ok(());
+        }
+        match self.script.next_step() {
+            Some(replayed) => self.state.update(replayed),
+            None => self.state.update(inputs),
+        }
+    }
+
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
+        self.state.get_rects()
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        if self.paused {
+            Ok(Some("PAUSED".to_string()))
+        } else {
+            self.state.get_text()
+        }
+    }
+
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        self.state.result()
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        self.state.events()
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        self.state.scores()
+    }
+
+    fn dump_state(&mut self) -> Option<String> {
+        self.state.dump_state()
+    }
+
+    fn save_state(&mut self) -> Option<String> {
+        self.state.save_state()
+    }
+
+    fn load_state(&mut self, _saved: &str) -> Result<(), String> {
+        Err("a game can't be loaded during a replay".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pong;
+
+    fn inputs(player: UserInput, second_player: UserInput) -> Inputs {
+        Inputs {
+            player,
+            second_player,
+        }
+    }
+
+    #[test]
+    fn source_round_trip() {
+        for &source in &[Source::Native, Source::Lambda(0), Source::Lambda(hash(b"pong"))] {
+            assert_eq!(Source::parse(&source.to_string()), Some(source));
+        }
+        assert_eq!(Source::parse("lambda"), None);
+        assert_eq!(Source::parse("native 12"), None);
+        assert_ne!(hash(b"nextState"), hash(b"nextStatf"));
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let header = Header {
+            source: Source::Native,
+            two_players: true,
+            difficulty: Difficulty::Hard,
+            rules: Some(pong::Tunables {
+                win_score: 5,
+                ..pong::Tunables::default()
+            }),
+        };
+        let ticks = [
+            inputs(UserInput::Up, UserInput::Nothing),
+            inputs(UserInput::Up, UserInput::Nothing),
+            inputs(UserInput::Serve, UserInput::Down),
+            inputs(UserInput::Nothing, UserInput::Down),
+            inputs(UserInput::Nothing, UserInput::Down),
+        ];
+
+        let mut out = Vec::new();
+        {
+            let state = pong::State::new(true, pong::Tunables::default(), Difficulty::Hard);
+            let mut recording = Recording::new(state, &mut out, header).unwrap();
+            for &t in &ticks {
+                recording.update(t).unwrap();
+            }
+        }
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("lambda-pong replay 1\nbackend native\nplayers 2\ndifficulty hard\n\
+                                  rules 8 60 4 5\n2 up nothing\n"));
+
+        let mut replay = Replay::parse(&text).unwrap();
+        assert_eq!(replay.header, header);
+        for &t in &ticks {
+            assert_eq!(replay.script.next_step(), Some(t));
+        }
+        assert_eq!(replay.script.next_step(), None);
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let played = [inputs(UserInput::Down, UserInput::Nothing); 100];
+        let mut out = Vec::new();
+        {
+            let header = Header {
+                source: Source::Native,
+                two_players: false,
+                difficulty: Difficulty::Normal,
+                rules: Some(pong::Tunables::default()),
+            };
+            let state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+            let mut recording = Recording::new(state, &mut out, header).unwrap();
+            for &t in &played {
+                recording.update(t).unwrap();
+            }
+        }
+        let mut original = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        for &t in &played {
+            original.update(t).unwrap();
+        }
+
+        let replay = Replay::parse(std::str::from_utf8(&out).unwrap()).unwrap();
+        let state = pong::State::new(false, pong::Tunables::default(), Difficulty::Normal);
+        let mut replaying = Replaying::new(state, replay.script);
+        for _ in 0..played.len() {
+            replaying.update(inputs(UserInput::Up, UserInput::Nothing)).unwrap();
+        }
+        assert_eq!(replaying.get_rects().unwrap(), original.get_rects().unwrap());
+    }
+
+    #[test]
+    fn invalid_replays() {
+        assert!(Replay::parse("").is_err());
+        assert!(Replay::parse("1 up").is_err());
+        assert_eq!(Replay::parse("lambda-pong replay 2\n").err(),
+                   Some("unsupported replay version '2'".to_string()));
+        assert!(Replay::parse("lambda-pong replay 1\nbackend native\nplayers 3\n").is_err());
+        assert_eq!(Replay::parse("lambda-pong replay 1\nbackend native\nplayers 1\ndifficulty easy\n\
+                                  rules -\n1 jump").err(),
+                   Some("line 6: unknown input 'jump'".to_string()));
+        assert_eq!(Replay::parse("lambda-pong replay 1\nbackend native\nplayers 1\ndifficulty 9\n").err(),
+                   Some("invalid difficulty '9'".to_string()));
+        assert_eq!(Replay::parse("lambda-pong replay 1\nbackend native\nplayers 1\ndifficulty easy\n1 up").err(),
+                   Some("expected 'rules ' in the header".to_string()));
+        assert_eq!(Replay::parse("lambda-pong replay 1\nbackend native\nplayers 1\ndifficulty easy\n\
+                                  rules 20 60 10 0\n").err(),
+                   Some("invalid rules '20 60 10 0'".to_string()));
+    }
+}
@@ -0,0 +1,258 @@
+// snapshot.rs: saves a game to a file, to resume it later.
+//
+// Snapshots are text: a header like a replay's, saying what the game was
+// played with, followed by the backend's state, e.g.
+//
+//     lambda-pong snapshot 1
+//     backend lambda 9f3c0a61d2e4b857
+//     players 1
+//     difficulty normal
+//     rules -
+//     <the state>
+//
+// The native backend's state is a field per line, and the lambda backend's
+// the state term, as the interpreter printed it. A snapshot only loads into
+// the same game: the same backend and source file, the same number of players
+// and difficulty, and the same rules.
+//
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    game::GameState,
+    replay::{self, Header, Source},
+};
+
+const MAGIC:&str = "lambda-pong snapshot";
+const VERSION:u32 = 1;
+
+pub struct Snapshot {
+    pub header: Header,
+    pub state: String,
+}
+
+impl Snapshot {
+    fn write(&self) -> Vec<u8> {
+        let mut out = format!("{} {}\n", MAGIC, VERSION).into_bytes();
+        // writing to a Vec can't fail.
+        let _ = self.header.write_fields(&mut out);
+        out.extend_from_slice(self.state.as_bytes());
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Snapshot, String> {
+        let mut lines = text.lines().enumerate();
+        let version = replay::header_value(&mut lines, MAGIC)
+            .map_err(|_| "not a snapshot file".to_string())?;
+        if version.parse() != Ok(VERSION) {
+            return Err(format!("unsupported snapshot version '{}'", version));
+        }
+        let header = Header::parse_fields(&mut lines)?;
+        let state = lines.map(|(_, line)| line).collect::<Vec<_>>().join("\n");
+        if state.trim().is_empty() {
+            return Err("the state is missing".to_string());
+        }
+        Ok(Snapshot { header, state })
+    }
+
+    pub fn read(filename: &Path) -> Result<Snapshot, String> {
+        fs::read_to_string(filename)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Snapshot::parse(&text))
+    }
+
+    /// Check that the snapshot was saved from the game with the given
+    /// header, so that it can be loaded into it.
+    ///
+    pub fn check(&self, header: &Header) -> Result<(), String> {
+        let saved = &self.header;
+        match (saved.source, header.source) {
+            (Source::Lambda(saved), Source::Lambda(current)) if saved != current => {
+                return Err("the game was saved with another lambda source file".to_string());
+            },
+            (saved, current) if saved != current => {
+                return Err(format!("the game was saved with another backend ({})", saved));
+            },
+            _ => {},
+        }
+        if saved.two_players != header.two_players {
+            return Err(format!("the game was saved in {}-player mode",
+                               if saved.two_players { 2 } else { 1 }));
+        }
+        if saved.difficulty != header.difficulty {
+            return Err(format!("the game was saved at difficulty '{}'", saved.difficulty.name()));
+        }
+        if saved.rules != header.rules {
+            return Err("the game was saved with other [native] parameters".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where the game is saved to and loaded from during the game, with the
+/// header of the game being played.
+///
+pub struct SnapshotFile {
+    path: PathBuf,
+    header: Header,
+}
+
+impl SnapshotFile {
+    pub fn new(path: PathBuf, header: Header) -> SnapshotFile {
+        SnapshotFile { path, header }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save<S: GameState>(&self, state: &mut S) -> Result<(), String> {
+        let snapshot = Snapshot {
+            header: self.header,
+            state: state.save_state().ok_or("this game can't be saved")?,
+        };
+        fs::write(&self.path, snapshot.write())
+            .map_err(|e| format!("failed to write '{}': {}", self.path.display(), e))
+    }
+
+    pub fn load<S: GameState>(&self, state: &mut S) -> Result<(), String> {
+        let snapshot = Snapshot::read(&self.path)
+            .map_err(|e| format!("failed to read '{}': {}", self.path.display(), e))?;
+        snapshot.check(&self.header)?;
+        state.load_state(&snapshot.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{Difficulty, Inputs, UserInput},
+        pong::{self, Tunables},
+    };
+
+    fn header(source: Source) -> Header {
+        Header {
+            source,
+            two_players: false,
+            difficulty: Difficulty::Hard,
+            rules: match source {
+                Source::Native => Some(Tunables::default()),
+                Source::Lambda(_) => None,
+            },
+        }
+    }
+
+    fn play(state: &mut pong::State, ticks: u32) {
+        for tick in 0..ticks {
+            state.update(Inputs {
+                player: if tick % 40 < 20 { UserInput::Up } else { UserInput::Down },
+                second_player: UserInput::Nothing,
+            }).unwrap();
+        }
+    }
+
+    #[test]
+    fn native_round_trip() {
+        let path = std::env::temp_dir().join(format!("pong_snapshot_{}.txt", std::process::id()));
+        let snapshots = SnapshotFile::new(path.clone(), header(Source::Native));
+        let new_state = || pong::State::new(false, Tunables::default(), Difficulty::Hard);
+
+        let mut state = new_state();
+        play(&mut state, 500);
+        snapshots.save(&mut state).unwrap();
+        let mut resumed = new_state();
+        snapshots.load(&mut resumed).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // the resumed game goes on exactly like the saved one.
+        assert_eq!(resumed.get_rects().unwrap(), state.get_rects().unwrap());
+        play(&mut state, 500);
+        play(&mut resumed, 500);
+        assert_eq!(resumed.get_rects().unwrap(), state.get_rects().unwrap());
+        assert_eq!(resumed.save_state(), state.save_state());
+    }
+
+    #[test]
+    fn mismatches_are_refused() {
+        let snapshot = Snapshot {
+            header: header(Source::Lambda(1)),
+            state: "\\x -> x".to_string(),
+        };
+        assert_eq!(snapshot.check(&header(Source::Lambda(2))),
+                   Err("the game was saved with another lambda source file".to_string()));
+        assert_eq!(snapshot.check(&header(Source::Native)),
+                   Err("the game was saved with another backend (lambda 0000000000000001)".to_string()));
+        let two_players = Header {
+            two_players: true,
+            ..header(Source::Lambda(1))
+        };
+        assert_eq!(snapshot.check(&two_players),
+                   Err("the game was saved in 1-player mode".to_string()));
+        let easy = Header {
+            difficulty: Difficulty::Easy,
+            ..header(Source::Lambda(1))
+        };
+        assert_eq!(snapshot.check(&easy), Err("the game was saved at difficulty 'hard'".to_string()));
+        assert_eq!(snapshot.check(&header(Source::Lambda(1))), Ok(()));
+
+        // the lambda state is kept as is.
+        let parsed = Snapshot::parse(&String::from_utf8(snapshot.write()).unwrap()).unwrap();
+        assert_eq!(parsed.header, snapshot.header);
+        assert_eq!(parsed.state, snapshot.state);
+    }
+
+    #[test]
+    fn invalid_native_states() {
+        let mut state = pong::State::new(false, Tunables::default(), Difficulty::Normal);
+        let saved = state.save_state().unwrap();
+        let other = Tunables {
+            win_score: 5,
+            ..Tunables::default()
+        };
+        let mut other_rules = pong::State::new(false, other, Difficulty::Normal);
+        assert_eq!(other_rules.load_state(&saved),
+                   Err("the game was saved with other [native] parameters".to_string()));
+        assert_eq!(state.load_state(&saved.replace("rng", "seed")),
+                   Err("'rng' is missing".to_string()));
+        assert_eq!(state.load_state(&saved.replace("ball_dir SE", "ball_dir up")),
+                   Err("invalid ball_dir 'up'".to_string()));
+        assert_eq!(state.load_state(&saved.replace("ball_y 300", "ball_y 900")),
+                   Err("the ball at (400, 900) is off the screen".to_string()));
+        assert_eq!(state.load_state(&saved.replace("ball_accel 1", "ball_accel 2147483647")),
+                   Err("invalid ball_accel '2147483647'".to_string()));
+        assert_eq!(state.load_state(&saved.replace("ball_accel 1", "ball_accel 0")),
+                   Err("invalid ball_accel '0'".to_string()));
+        assert_eq!(state.load_state(&saved.replace("cpu_y 270", "cpu_y 541")),
+                   Err("the paddle at cpu_y 541 is off the screen".to_string()));
+        assert_eq!(state.load_state(&saved.replace("player_score 0", "player_score 11")),
+                   Err("player_score 11 is more than win_score".to_string()));
+        assert_eq!(state.load_state(&format!("{}paused true\n", saved)),
+                   Err("'paused' is given twice".to_string()));
+        assert_eq!(Snapshot::parse("lambda-pong snapshot 1\nbackend native\nplayers 1\ndifficulty easy\n\
+                                    rules -\n").err(),
+                   Some("the state is missing".to_string()));
+        let mut rules = header(Source::Native);
+        rules.rules = Some(other);
+        let snapshot = Snapshot {
+            header: rules,
+            state: saved.clone(),
+        };
+        assert_eq!(snapshot.check(&header(Source::Native)),
+                   Err("the game was saved with other [native] parameters".to_string()));
+        let no_rules = Snapshot {
+            header: Header {
+                rules: None,
+                ..header(Source::Native)
+            },
+            state: saved.clone(),
+        };
+        assert_eq!(no_rules.check(&header(Source::Native)),
+                   Err("the game was saved with other [native] parameters".to_string()));
+        assert_eq!(Snapshot::parse("lambda-pong replay 2\n").err(),
+                   Some("not a snapshot file".to_string()));
+    }
+}
@@ -0,0 +1,289 @@
+// sound.rs: beeps for the game's events, played through SDL or saved as WAV.
+//
+// Every event is a short square wave, like in the original arcade game. The
+// same synthesizer feeds both SDL's audio callback, in real time, and the WAV
+// writer, which follows the game's ticks instead, so that its output doesn't
+// depend on how fast frames are drawn.
+//
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+};
+
+use sdl2::{
+    Sdl,
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+};
+
+use crate::game::{Frame, GameEvent, Renderer};
+
+pub const SAMPLE_RATE:u32 = 44100;
+
+// of a single beep; beeps playing at once are added together.
+const AMPLITUDE:i32 = 4000;
+
+// samples per SDL audio callback; small, so that beeps aren't late.
+const BUFFER_SAMPLES:u16 = 512;
+
+// the WAV header's size, and where its two sizes are.
+const WAV_HEADER_LEN:u32 = 44;
+const RIFF_SIZE_OFFSET:u64 = 4;
+const DATA_SIZE_OFFSET:u64 = 40;
+
+// frequency in Hz and duration in milliseconds of each event's beep.
+fn tone(event: GameEvent) -> (u32, u32) {
+    match event {
+        GameEvent::PaddleHit => (459, 32),
+        GameEvent::WallBounce => (226, 16),
+        GameEvent::Score => (490, 257),
+    }
+}
+
+struct Beep {
+    frequency: u32,
+    // samples played so far, and in total.
+    played: u32,
+    len: u32,
+}
+
+/// Mixes square waves into mono 16-bit samples.
+pub struct Synth {
+    sample_rate: u32,
+    beeps: Vec<Beep>,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Synth {
+        Synth {
+            sample_rate,
+            beeps: Vec::new(),
+        }
+    }
+
+    /// Start the event's beep.
+    pub fn play(&mut self, event: GameEvent) {
+        let (frequency, millis) = tone(event);
+        self.beeps.push(Beep {
+            frequency,
+            played: 0,
+            len: (self.sample_rate as u64 * millis as u64 / 1000) as u32,
+        });
+    }
+
+    /// Fill out with the next samples, which are silent once every beep is
+    /// over.
+    pub fn fill(&mut self, out: &mut [i16]) {
+        for sample in out.iter_mut() {
+            let mut mixed = 0;
+            for beep in self.beeps.iter_mut().filter(|beep| beep.played < beep.len) {
+                // which half of the wave's period we're in.
+                let half = beep.played as u64 * beep.frequency as u64 * 2 / self.sample_rate as u64;
+                mixed += if half.is_multiple_of(2) { AMPLITUDE } else { -AMPLITUDE };
+                beep.played += 1;
+            }
+            *sample = mixed.max(i16::MIN as i32).min(i16::MAX as i32) as i16;
+        }
+        self.beeps.retain(|beep| beep.played < beep.len);
+    }
+}
+
+impl AudioCallback for Synth {
+    type Channel = i16;
+
+    fn callback(&mut self, out: &mut [i16]) {
+        self.fill(out);
+    }
+}
+
+/// Plays the frames' events through SDL's audio, as they come.
+pub struct Speaker {
+    device: AudioDevice<Synth>,
+}
+
+impl Speaker {
+    pub fn open(sdl_context: &Sdl) -> Result<Speaker, String> {
+        let audio = sdl_context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: Some(BUFFER_SAMPLES),
+        };
+        let device = audio.open_playback(None, &desired, |spec| Synth::new(spec.freq as u32))?;
+        device.resume();
+        Ok(Speaker { device })
+    }
+}
+
+impl Renderer for Speaker {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        if !frame.events.is_empty() {
+            let mut synth = self.device.lock();
+            for &(_, event) in frame.events {
+                synth.play(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Saves the frames' events as a mono 16-bit WAV file, with as many samples
+/// as the ticks so far take at tick_rate ticks per second.
+///
+pub struct WavWriter<W: Write + Seek = BufWriter<File>> {
+    out: W,
+    synth: Synth,
+    tick_rate: u32,
+    samples: u64,
+}
+
+impl WavWriter {
+    pub fn create(filename: &str, tick_rate: u32) -> io::Result<WavWriter> {
+        WavWriter::new(BufWriter::new(File::create(filename)?), tick_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, tick_rate: u32) -> io::Result<WavWriter<W>> {
+        // the sizes are filled in when done.
+        write_wav_header(&mut out, 0)?;
+        Ok(WavWriter {
+            out,
+            synth: Synth::new(SAMPLE_RATE),
+            tick_rate: tick_rate.max(1),
+            samples: 0,
+        })
+    }
+
+    fn write_until(&mut self, tick: u64) -> io::Result<()> {
+        let end = tick * SAMPLE_RATE as u64 / self.tick_rate as u64;
+        let mut buffer = [0; BUFFER_SAMPLES as usize];
+        while self.samples < end {
+            let len = (end - self.samples).min(buffer.len() as u64) as usize;
+            self.synth.fill(&mut buffer[..len]);
+            for sample in &buffer[..len] {
+                self.out.write_all(&sample.to_le_bytes())?;
+            }
+            self.samples += len as u64;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let data_len = (self.samples * 2) as u32;
+        self.out.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.out.write_all(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.out.write_all(&data_len.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write + Seek> Renderer for WavWriter<W> {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        // each event is heard from the end of its own tick, however many
+        // ticks the frame ran.
+        for &(tick, event) in frame.events {
+            self.write_until(tick).map_err(|e| e.to_string())?;
+            self.synth.play(event);
+        }
+        self.write_until(frame.tick).map_err(|e| e.to_string())
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("failed to write audio: {}", e);
+        }
+    }
+}
+
+fn write_wav_header<W: Write>(out: &mut W, data_len: u32) -> io::Result<()> {
+    const CHANNELS:u16 = 1;
+    const BITS:u16 = 16;
+    let block_align = CHANNELS * BITS / 8;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM.
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&CHANNELS.to_le_bytes())?;
+    out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    out.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&BITS.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn square_wave() {
+        // a 4 Hz beep at 16 samples per second: 2 samples up, 2 down.
+        let mut synth = Synth::new(16);
+        synth.beeps.push(Beep { frequency: 4, played: 0, len: 6 });
+        let mut out = [1; 8];
+        synth.fill(&mut out);
+        let (up, down) = (AMPLITUDE as i16, -AMPLITUDE as i16);
+        assert_eq!(out, [up, up, down, down, up, up, 0, 0]);
+        assert!(synth.beeps.is_empty());
+    }
+
+    #[test]
+    fn beeps_are_mixed() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        for _ in 0..10 {
+            synth.play(GameEvent::Score);
+        }
+        let mut out = [0; 1];
+        synth.fill(&mut out);
+        assert_eq!(out[0], i16::MAX);
+    }
+
+    fn frame(tick: u64, events: &[(u64, GameEvent)]) -> Frame<'_> {
+        Frame {
+            tick,
+            rects: &[],
+            text: None,
+            events,
+            inputs: &[],
+            scores: None,
+            overlay: None,
+        }
+    }
+
+    #[test]
+    fn wav_output() {
+        let mut out = Cursor::new(Vec::new());
+        {
+            let mut wav = WavWriter::new(&mut out, 60).unwrap();
+            // one frame for the three ticks, with a hit on the first.
+            wav.draw(&frame(3, &[(1, GameEvent::PaddleHit)])).unwrap();
+        }
+        let bytes = out.into_inner();
+        // three ticks' worth of samples, at 735 samples per tick.
+        let data_len = 3 * 735 * 2;
+        assert_eq!(bytes.len(), WAV_HEADER_LEN as usize + data_len);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &(36 + data_len as u32).to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[40..44], &(data_len as u32).to_le_bytes());
+
+        let sample = |i: usize| {
+            let at = WAV_HEADER_LEN as usize + i * 2;
+            i16::from_le_bytes([bytes[at], bytes[at + 1]])
+        };
+        // silent until the end of the first tick, then beeping.
+        assert_eq!(sample(734), 0);
+        assert_eq!(sample(735), AMPLITUDE as i16);
+    }
+}
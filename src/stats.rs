@@ -0,0 +1,201 @@
+// stats.rs: how long each part of every frame takes, to compare backends.
+//
+// The game loop times each frame's updates, game over checks, getting the
+// rectangles and text, and drawing. The timings can be shown on screen as
+// they come, saved as CSV, and summarized with percentiles at exit.
+//
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+// how many of the latest frames the overlay averages over.
+const OVERLAY_FRAMES:usize = 60;
+
+/// The time spent in each part of a single frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTimes {
+    /// How many ticks were run in this frame.
+    pub ticks: u32,
+    pub update: Duration,
+    pub game_over: Duration,
+    /// Getting both the rectangles and the text.
+    pub get_rects: Duration,
+    /// Drawing and presenting the frame.
+    pub present: Duration,
+    /// From the start of this frame to the start of the next one, including
+    /// waiting for vsync or the next tick.
+    pub frame: Duration,
+}
+
+// a part of the frame, by name.
+type Column = (&'static str, fn(&FrameTimes) -> Duration);
+
+const COLUMNS:[Column; 5] = [
+    ("update", |t| t.update),
+    ("game_over", |t| t.game_over),
+    ("get_rects", |t| t.get_rects),
+    ("present", |t| t.present),
+    ("frame", |t| t.frame),
+];
+
+pub struct Stats {
+    frames: Vec<FrameTimes>,
+    overlay: bool,
+}
+
+impl Stats {
+    /// Collect the frames' timings, showing them on screen if overlay is true.
+    pub fn new(overlay: bool) -> Stats {
+        Stats {
+            frames: Vec::new(),
+            overlay,
+        }
+    }
+
+    pub fn record(&mut self, times: FrameTimes) {
+        self.frames.push(times);
+    }
+
+    /// The text to show over the game, if the overlay is on.
+    pub fn overlay(&self) -> Option<String> {
+        if !self.overlay {
+            return None;
+        }
+        let recent = &self.frames[self.frames.len().saturating_sub(OVERLAY_FRAMES)..];
+        if recent.is_empty() {
+            return Some("FPS -".to_string());
+        }
+        let average = |column: fn(&FrameTimes) -> Duration| {
+            recent.iter().map(column).sum::<Duration>() / recent.len() as u32
+        };
+        let frame = average(|t| t.frame);
+        let fps = if frame > Duration::from_secs(0) {
+            1.0 / frame.as_secs_f64()
+        } else {
+            0.0
+        };
+        Some(format!("FPS {:.1}  FRAME {:.1}MS  UPDATE {:.1}MS  DRAW {:.1}MS",
+                     fps,
+                     millis(frame),
+                     millis(average(|t| t.update)),
+                     millis(average(|t| t.get_rects) + average(|t| t.present))))
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "frame,ticks")?;
+        for (name, _) in COLUMNS.iter() {
+            write!(out, ",{}_us", name)?;
+        }
+        writeln!(out)?;
+        for (i, times) in self.frames.iter().enumerate() {
+            write!(out, "{},{}", i, times.ticks)?;
+            for (_, column) in COLUMNS.iter() {
+                write!(out, ",{}", column(times).as_micros())?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// A table of percentiles of each part of the frames, in milliseconds.
+    pub fn summary(&self) -> String {
+        let ticks: u64 = self.frames.iter().map(|t| t.ticks as u64).sum();
+        let mut summary = format!("{} frames, {} ticks\n", self.frames.len(), ticks);
+        summary.push_str(&format!("{:<10}{:>10}{:>10}{:>10}{:>10}{:>10}\n",
+                                  "(ms)", "mean", "p50", "p90", "p99", "max"));
+        for (name, column) in COLUMNS.iter() {
+            let mut times: Vec<Duration> = self.frames.iter().map(column).collect();
+            times.sort();
+            let mean = match times.len() {
+                0 => Duration::from_secs(0),
+                len => times.iter().sum::<Duration>() / len as u32,
+            };
+            summary.push_str(&format!("{:<10}{:>10.3}{:>10.3}{:>10.3}{:>10.3}{:>10.3}\n",
+                                      name,
+                                      millis(mean),
+                                      millis(percentile(&times, 50.0)),
+                                      millis(percentile(&times, 90.0)),
+                                      millis(percentile(&times, 99.0)),
+                                      millis(percentile(&times, 100.0))));
+        }
+        summary
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// The nearest-rank percentile of sorted times, or zero if there are none.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::from_secs(0);
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn percentiles() {
+        let times: Vec<Duration> = (1..=10).map(ms).collect();
+        assert_eq!(percentile(&times, 50.0), ms(5));
+        assert_eq!(percentile(&times, 90.0), ms(9));
+        assert_eq!(percentile(&times, 99.0), ms(10));
+        assert_eq!(percentile(&times, 0.0), ms(1));
+        assert_eq!(percentile(&[], 50.0), ms(0));
+    }
+
+    #[test]
+    fn csv_output() {
+        let mut stats = Stats::new(false);
+        stats.record(FrameTimes {
+            ticks: 2,
+            update: ms(3),
+            frame: Duration::from_micros(16_667),
+            ..FrameTimes::default()
+        });
+        let mut out = Vec::new();
+        stats.write_csv(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "frame,ticks,update_us,game_over_us,get_rects_us,present_us,frame_us\n\
+                    0,2,3000,0,0,0,16667\n");
+    }
+
+    #[test]
+    fn overlay() {
+        let mut stats = Stats::new(true);
+        for _ in 0..3 {
+            stats.record(FrameTimes {
+                ticks: 1,
+                update: ms(1),
+                present: ms(2),
+                frame: ms(20),
+                ..FrameTimes::default()
+            });
+        }
+        assert_eq!(stats.overlay(),
+                   Some("FPS 50.0  FRAME 20.0MS  UPDATE 1.0MS  DRAW 2.0MS".to_string()));
+        assert_eq!(Stats::new(false).overlay(), None);
+    }
+
+    #[test]
+    fn summary_lists_every_column() {
+        let mut stats = Stats::new(false);
+        stats.record(FrameTimes::default());
+        let summary = stats.summary();
+        assert!(summary.starts_with("1 frames, 0 ticks\n"));
+        for (name, _) in COLUMNS.iter() {
+            assert!(summary.contains(name));
+        }
+    }
+}
@@ -0,0 +1,704 @@
+// stream.rs: publishes the game as it's played, for spectators to watch,
+// e.g. on a projector while the interpreter runs on another machine.
+//
+// Every frame is sent to every spectator as a line of JSON: the same line as
+// in headless mode, plus the scores, if the backend knows them, and the
+// inputs and events of the ticks since the last frame, e.g.
+//
+//     {"tick":42,"rects":[[10,20,4,4]],"text":null,"scores":[1,0],"inputs":[["up","nothing"]],"events":["paddle_hit"]}
+//
+// Spectators can come and go at any time. Frames are sent from a thread, so
+// that a slow spectator doesn't slow the game down; if the spectators can't
+// keep up, they skip frames.
+//
+
+use std::{
+    fmt::{self, Write as _},
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    game::{Error, Frame, GameEvent, GameState, Inputs, Renderer, UserInput},
+    headless,
+    net,
+};
+
+// how many frames can wait to be sent before new ones are skipped.
+const FRAME_BACKLOG:usize = 60;
+
+// how long a spectator has to take a frame before it's dropped.
+const WRITE_TIMEOUT:Duration = Duration::from_secs(1);
+
+// far longer than any frame; a screen full of rectangles is about 25 bytes
+// each.
+const MAX_FRAME:u64 = 1024 * 1024;
+
+/// Where a game is published: a TCP address, as host:port, or a Unix
+/// socket, as unix:<path>.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Address {
+    pub fn parse(s: &str) -> Result<Address, String> {
+        match s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) if !path.is_empty() => Ok(Address::Unix(PathBuf::from(path))),
+            #[cfg(not(unix))]
+            Some(_) => Err("Unix sockets aren't supported on this platform".to_string()),
+            #[cfg(unix)]
+            Some(_) => Err("expected a path after 'unix:'".to_string()),
+            None if s.contains(':') => Ok(Address::Tcp(s.to_string())),
+            None => Err(format!("expected host:port or unix:<path>, not '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn bind(address: &Address) -> io::Result<Listener> {
+        let listener = match address {
+            Address::Tcp(address) => Listener::Tcp(TcpListener::bind(address)?),
+            #[cfg(unix)]
+            Address::Unix(path) => Listener::Unix(UnixListener::bind(path)?, path.clone()),
+        };
+        // spectators are only let in between frames.
+        match listener {
+            Listener::Tcp(ref listener) => listener.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Listener::Unix(ref listener, _) => listener.set_nonblocking(true)?,
+        }
+        Ok(listener)
+    }
+
+    // The next spectator waiting to connect, if any.
+    fn accept(&self) -> io::Result<Option<Box<dyn Write + Send>>> {
+        let accepted = match self {
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Ok(Box::new(stream) as Box<dyn Write + Send>)
+            }),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Ok(Box::new(stream) as Box<dyn Write + Send>)
+            }),
+        };
+        match accepted {
+            Ok(spectator) => Ok(Some(spectator)),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// Don't leave the socket's file behind.
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Sends every frame it draws to whoever is spectating.
+pub struct Publisher {
+    frames: Option<SyncSender<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Publisher {
+    pub fn bind(address: &Address) -> io::Result<Publisher> {
+        let listener = Listener::bind(address)?;
+        let (sender, receiver) = mpsc::sync_channel(FRAME_BACKLOG);
+        let thread = thread::spawn(move || publish(listener, receiver));
+        Ok(Publisher {
+            frames: Some(sender),
+            thread: Some(thread),
+        })
+    }
+}
+
+// Send every frame to every spectator, letting new ones in before each
+// frame, until the publisher is dropped.
+fn publish(listener: Listener, frames: Receiver<String>) {
+    let mut spectators = Vec::new();
+    for line in frames {
+        while let Ok(Some(spectator)) = listener.accept() {
+            spectators.push(spectator);
+        }
+        spectators.retain_mut(|spectator| {
+            writeln!(spectator, "{}", line).and_then(|_| spectator.flush()).is_ok()
+        });
+    }
+}
+
+impl Renderer for Publisher {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        if let Some(ref frames) = self.frames {
+            // if it's full, the spectators are too far behind for this frame.
+            let _ = frames.try_send(frame_line(frame));
+        }
+        Ok(())
+    }
+
+    fn wants_scores(&self) -> bool {
+        true
+    }
+}
+
+// Send the last frames, e.g. the game over, before going.
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        self.frames = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn frame_line(frame: &Frame) -> String {
+    let mut line = headless::json_line(frame.tick, frame.rects, frame.text);
+    // the headless line's closing brace comes after the new fields.
+    line.pop();
+    match frame.scores {
+        Some((left, right)) => {
+            let _ = write!(line, ",\"scores\":[{},{}]", left, right);
+        },
+        None => line.push_str(",\"scores\":null"),
+    }
+    line.push_str(",\"inputs\":[");
+    for (i, inputs) in frame.inputs.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "[\"{}\",\"{}\"]", inputs.player.name(), inputs.second_player.name());
+    }
+    line.push_str("],\"events\":[");
+    for (i, (_, event)) in frame.events.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "\"{}\"", event.name());
+    }
+    line.push_str("]}");
+    line
+}
+
+/// A frame, as a spectator sees it.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Published {
+    rects: Vec<Rect>,
+    text: Option<String>,
+    scores: Option<(usize, usize)>,
+    events: Vec<GameEvent>,
+}
+
+/// Watches a published game: every update shows the latest frame received.
+/// The game is over once the stream ends, or the spectator quits.
+pub struct Spectating {
+    frames: Receiver<Result<Published, String>>,
+    latest: Published,
+    events: Vec<GameEvent>,
+    ended: bool,
+}
+
+impl Spectating {
+    pub fn connect(address: &Address) -> io::Result<Spectating> {
+        Ok(match address {
+            Address::Tcp(address) => Spectating::new(TcpStream::connect(address)?),
+            #[cfg(unix)]
+            Address::Unix(path) => Spectating::new(UnixStream::connect(path)?),
+        })
+    }
+
+    // The frames are read from a thread, so that the window stays
+    // responsive however often they come. A frame that's too long ends the
+    // stream, as one that doesn't parse does.
+    fn new<R: Read + Send + 'static>(stream: R) -> Spectating {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream);
+            loop {
+                let frame = match net::read_line(&mut reader, MAX_FRAME) {
+                    Ok(Some(line)) => parse_frame(&line),
+                    Err(ref e) if e.kind() == io::ErrorKind::InvalidData => Err(e.to_string()),
+                    Ok(None) | Err(_) => break,
+                };
+                let failed = frame.is_err();
+                if sender.send(frame).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Spectating {
+            frames: receiver,
+            latest: Published::default(),
+            events: Vec::new(),
+            ended: false,
+        }
+    }
+}
+
+impl GameState for Spectating {
+    fn game_over(&mut self) -> Result<bool, Error> {
+        Ok(self.ended)
+    }
+
+    fn update(&mut self, inputs: Inputs) -> Result<(), Error> {
+        if inputs.player == UserInput::Quit {
+            self.ended = true;
+        }
+        loop {
+            match self.frames.try_recv() {
+                Ok(Ok(frame)) => {
+                    self.events.extend_from_slice(&frame.events);
+                    self.latest = frame;
+                },
+                Ok(Err(e)) => return Err(Error::Network(format!("invalid frame: {}", e))),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    self.ended = true;
+                    return Ok(());
+                },
+            }
+        }
+    }
+
+    fn get_rects(&mut self) -> Result<Vec<Rect>, Error> {
+        Ok(self.latest.rects.clone())
+    }
+
+    fn get_text(&mut self) -> Result<Option<String>, Error> {
+        Ok(self.latest.text.clone())
+    }
+
+    fn result(&mut self) -> Result<Option<String>, Error> {
+        Ok(Some(match self.latest.scores {
+            Some((left, right)) => format!("STREAM ENDED  {} - {}", left, right),
+            None => "STREAM ENDED".to_string(),
+        }))
+    }
+
+    fn events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn scores(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        Ok(self.latest.scores)
+    }
+}
+
+// Only the fields spectators need are read; unknown events are skipped, like
+// the lambda backend's unknown tags.
+fn parse_frame(line: &str) -> Result<Published, String> {
+    let fields = match Json::parse(line)? {
+        Json::Object(fields) => fields,
+        _ => return Err("expected an object".to_string()),
+    };
+    let field = |name: &str| fields.iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value);
+    let error = |name: &str| format!("invalid '{}'", name);
+
+    let mut frame = Published::default();
+    for rect in field("rects").and_then(Json::as_array).ok_or_else(|| error("rects"))? {
+        match rect.as_array().map(|rect| rect.iter().map(Json::as_int).collect::<Vec<_>>()) {
+            Some(ref rect) if rect.len() == 4 => match rect[..] {
+                [Some(x), Some(y), Some(width), Some(height)] if width >= 0 && height >= 0 => {
+                    frame.rects.push(Rect::new(x as i32, y as i32, width as u32, height as u32));
+                },
+                _ => return Err(error("rects")),
+            },
+            _ => return Err(error("rects")),
+        }
+    }
+    frame.text = match field("text") {
+        None | Some(Json::Null) => None,
+        Some(Json::String(text)) => Some(text.clone()),
+        Some(_) => return Err(error("text")),
+    };
+    frame.scores = match field("scores") {
+        None | Some(Json::Null) => None,
+        Some(Json::Array(scores)) => match scores[..] {
+            [Json::Number(left), Json::Number(right)] if left >= 0 && right >= 0 => {
+                Some((left as usize, right as usize))
+            },
+            _ => return Err(error("scores")),
+        },
+        Some(_) => return Err(error("scores")),
+    };
+    if let Some(events) = field("events") {
+        for event in events.as_array().ok_or_else(|| error("events"))? {
+            match event {
+                Json::String(name) => frame.events.extend(GameEvent::from_name(name)),
+                _ => return Err(error("events")),
+            }
+        }
+    }
+    Ok(frame)
+}
+
+// Just enough JSON for the frames: numbers are integers.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("expected the end of the line"));
+        }
+        Ok(value)
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match *self {
+            Json::Number(n) if i32::MIN as i64 <= n && n <= i32::MAX as i64 => Some(n),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("offset {}: {}", self.pos, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.text.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    // Skip the given byte, after any whitespace, if it's next.
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.text.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.pos..].starts_with(keyword.as_bytes()) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.text.get(self.pos) {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        values.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or ']'"));
+                        }
+                    }
+                }
+                Ok(Json::Array(values))
+            },
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return Err(self.error("expected ':'"));
+                        }
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or '}'"));
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            },
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        if self.text[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        while let Some(b'0'..=b'9') = self.text.get(self.pos) {
+            self.pos += 1;
+        }
+        // the text is valid UTF-8, and this part is ASCII.
+        std::str::from_utf8(&self.text[start..self.pos]).ok()
+            .and_then(|n| n.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("expected an integer"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.text.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = match self.text.get(self.pos) {
+                None => return Err(self.error("unfinished string")),
+                Some(&byte) => byte,
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self.text.get(self.pos).copied();
+                    self.pos += 1;
+                    let c = match escaped {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'u') => {
+                            let code = self.text.get(self.pos..self.pos + 4)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid \\u escape"))?;
+                            self.pos += 4;
+                            code
+                        },
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::Difficulty, pong::{self, Tunables}};
+
+    fn frame<'a>(rects: &'a [Rect],
+                 inputs: &'a [Inputs],
+                 events: &'a [(u64, GameEvent)]) -> Frame<'a> {
+        Frame {
+            tick: 7,
+            rects,
+            text: Some("A \"B\""),
+            events,
+            inputs,
+            scores: Some((2, 10)),
+            overlay: None,
+        }
+    }
+
+    #[test]
+    fn frame_lines() {
+        let rects = [Rect::new(1, -2, 3, 4)];
+        let inputs = [Inputs {
+            player: UserInput::Up,
+            second_player: UserInput::Nothing,
+        }];
+        let events = [(6, GameEvent::WallBounce), (7, GameEvent::Score)];
+        let line = frame_line(&frame(&rects, &inputs, &events));
+        assert_eq!(line, concat!(r#"{"tick":7,"rects":[[1,-2,3,4]],"text":"A \"B\"","#,
+                                 r#""scores":[2,10],"inputs":[["up","nothing"]],"#,
+                                 r#""events":["wall_bounce","score"]}"#));
+        assert_eq!(parse_frame(&line), Ok(Published {
+            rects: rects.to_vec(),
+            text: Some("A \"B\"".to_string()),
+            scores: Some((2, 10)),
+            events: vec![GameEvent::WallBounce, GameEvent::Score],
+        }));
+
+        // headless lines are frames too.
+        let headless = headless::json_line(1, &rects, None);
+        assert_eq!(parse_frame(&headless).unwrap().rects, rects);
+    }
+
+    #[test]
+    fn invalid_frames() {
+        assert_eq!(parse_frame(r#"{"rects":[[1,2,3]]}"#), Err("invalid 'rects'".to_string()));
+        assert_eq!(parse_frame(r#"{"rects":[[1,2,-3,4]]}"#), Err("invalid 'rects'".to_string()));
+        assert_eq!(parse_frame(r#"{"text":null}"#), Err("invalid 'rects'".to_string()));
+        assert_eq!(parse_frame(r#"{"rects":[],"scores":[1]}"#), Err("invalid 'scores'".to_string()));
+        assert_eq!(parse_frame(r#"{"rects":[],"text":1}"#), Err("invalid 'text'".to_string()));
+        assert_eq!(parse_frame("[]"), Err("expected an object".to_string()));
+        assert_eq!(parse_frame(r#"{"rects":[]"#), Err("offset 11: expected ',' or '}'".to_string()));
+        assert_eq!(parse_frame(r#"{"rects":[1.5]}"#), Err("offset 11: expected ',' or ']'".to_string()));
+        // unknown fields and events are fine.
+        assert!(parse_frame(r#"{"rects":[],"events":["explosion"],"new":{"a":[true]}}"#).is_ok());
+    }
+
+    #[test]
+    fn json_values() {
+        assert_eq!(Json::parse(r#" [null, true, -12, "é\n", {}] "#), Ok(Json::Array(vec![
+            Json::Null,
+            Json::Bool(true),
+            Json::Number(-12),
+            Json::String("é\n".to_string()),
+            Json::Object(Vec::new()),
+        ])));
+        assert!(Json::parse("nul").is_err());
+        assert!(Json::parse(r#""abc"#).is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn long_frames_are_refused() {
+        let lines = format!("{{\"rects\":[]}}\n[{}]\n", " ".repeat(MAX_FRAME as usize));
+        let mut spectating = Spectating::new(io::Cursor::new(lines));
+        let inputs = Inputs {
+            player: UserInput::Nothing,
+            second_player: UserInput::Nothing,
+        };
+        let error = loop {
+            match spectating.update(inputs) {
+                Ok(()) => thread::yield_now(),
+                Err(e) => break e.to_string(),
+            }
+        };
+        assert_eq!(error, format!("invalid frame: line longer than {} bytes", MAX_FRAME));
+    }
+
+    // Publish a few ticks of the native game, and check what a spectator
+    // sees.
+    fn watch(address: Address) {
+        let mut publisher = Publisher::bind(&address).unwrap();
+        let mut spectating = Spectating::connect(&address).unwrap();
+
+        let mut state = pong::State::new(false, Tunables::default(), Difficulty::Normal);
+        let inputs = Inputs {
+            player: UserInput::Up,
+            second_player: UserInput::Nothing,
+        };
+        for tick in 1..=5 {
+            state.update(inputs).unwrap();
+            let rects = state.get_rects().unwrap();
+            publisher.draw(&Frame {
+                tick,
+                rects: &rects,
+                text: None,
+                events: &[],
+                inputs: &[inputs],
+                scores: state.scores().unwrap(),
+                overlay: None,
+            }).unwrap();
+        }
+        // the last frames are sent before the publisher goes.
+        drop(publisher);
+
+        while !spectating.game_over().unwrap() {
+            spectating.update(inputs).unwrap();
+        }
+        assert_eq!(spectating.get_rects().unwrap(), state.get_rects().unwrap());
+        assert_eq!(spectating.scores().unwrap(), Some((0, 0)));
+        assert_eq!(spectating.result().unwrap(), Some("STREAM ENDED  0 - 0".to_string()));
+    }
+
+    #[test]
+    fn spectate_over_tcp() {
+        // find a free port first.
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        watch(Address::Tcp(format!("127.0.0.1:{}", port)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spectate_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("pong_stream_{}.sock", std::process::id()));
+        watch(Address::Unix(path.clone()));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(Address::parse("localhost:7000"), Ok(Address::Tcp("localhost:7000".to_string())));
+        #[cfg(unix)]
+        assert_eq!(Address::parse("unix:/tmp/pong.sock"),
+                   Ok(Address::Unix(PathBuf::from("/tmp/pong.sock"))));
+        assert!(Address::parse("unix:").is_err());
+        assert!(Address::parse("7000").is_err());
+    }
+}
@@ -0,0 +1,572 @@
+// terminal.rs: plays the game in a terminal instead of a window, e.g. over
+// SSH.
+//
+// The screen is scaled down to the terminal's size, with two pixels per
+// character using half blocks, and drawn with ANSI escape codes. The
+// terminal is put into raw mode with stty, so keys are read as they're
+// typed. Terminals don't tell when a key is released, so holding a key
+// relies on the terminal's key repeat: each press moves the paddle for a few
+// ticks. The colors are the config's, as near as the terminal's 256-color
+// palette gets.
+//
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use sdl2::rect::Rect;
+
+use crate::{
+    config::Colors,
+    debug::DebugCommand,
+    game::{Frame, InputSource, Inputs, Renderer, UserInput},
+    pong::{WINDOW_WIDTH, WINDOW_HEIGHT},
+};
+
+// how many ticks a paddle keeps moving after its key is pressed; long
+// enough to bridge the gaps between the terminal's key repeats.
+const HOLD_TICKS:u32 = 10;
+
+// how often to check whether the terminal was resized.
+const RESIZE_CHECK:Duration = Duration::from_secs(1);
+
+const ESC:u8 = 0x1b;
+
+// how long to wait for the rest of an escape sequence before taking an ESC
+// on its own as the Escape key.
+const ESC_TIMEOUT:Duration = Duration::from_millis(100);
+
+/// The screen, scaled down to a grid of characters, each made of two
+/// pixels stacked vertically.
+///
+pub struct Grid {
+    cols: usize,
+    rows: usize,
+    // cols * (rows * 2) pixels, row by row.
+    pixels: Vec<bool>,
+}
+
+impl Grid {
+    pub fn new(cols: usize, rows: usize) -> Grid {
+        Grid {
+            cols,
+            rows,
+            pixels: vec![false; cols * rows * 2],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel = false;
+        }
+    }
+
+    /// Fill the rectangle, given in the window's coordinates. Anything that
+    /// covers part of a pixel fills it, so that thin lines don't vanish.
+    ///
+    pub fn fill_rect(&mut self, rect: Rect) {
+        let height = self.rows * 2;
+        let scale = |pos: i64, window_size: i32, size: usize, round_up: bool| {
+            let num = pos * size as i64;
+            let scaled = if round_up {
+                (num + window_size as i64 - 1).div_euclid(window_size as i64)
+            } else {
+                num.div_euclid(window_size as i64)
+            };
+            scaled.max(0).min(size as i64) as usize
+        };
+        let left = scale(rect.x() as i64, WINDOW_WIDTH, self.cols, false);
+        let right = scale(rect.x() as i64 + rect.width() as i64, WINDOW_WIDTH, self.cols, true);
+        let top = scale(rect.y() as i64, WINDOW_HEIGHT, height, false);
+        let bottom = scale(rect.y() as i64 + rect.height() as i64, WINDOW_HEIGHT, height, true);
+
+        for y in top..bottom {
+            for x in left..right {
+                self.pixels[y * self.cols + x] = true;
+            }
+        }
+    }
+
+    /// The escape codes and characters that draw the grid in the given
+    /// colors, with the text, if any, written over its middle rows, a line
+    /// per row, and the overlay over its first one.
+    ///
+    pub fn render(&self, colors: &Colors, text: Option<&str>, overlay: Option<&str>) -> String {
+        let text_lines: Vec<&str> = text.map_or(Vec::new(), |text| text.split('\n').collect());
+        let text_top = (self.rows / 2).saturating_sub(text_lines.len().saturating_sub(1) / 2);
+        // the colors, from the top left corner.
+        let mut out = format!("{}[38;5;{}m{}[48;5;{}m{}[H",
+                              ESC as char, ansi_color(colors.foreground),
+                              ESC as char, ansi_color(colors.background),
+                              ESC as char);
+        for row in 0..self.rows {
+            let mut line: Vec<char> = (0..self.cols)
+                .map(|col| {
+                    let top = self.pixels[row * 2 * self.cols + col];
+                    let bottom = self.pixels[(row * 2 + 1) * self.cols + col];
+                    match (top, bottom) {
+                        (false, false) => ' ',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (true, true) => '█',
+                    }
+                })
+                .collect();
+            if let Some(text) = row.checked_sub(text_top).and_then(|i| text_lines.get(i)) {
+                let len = text.chars().count().min(self.cols);
+                let start = (self.cols - len) / 2;
+                for (i, c) in text.chars().take(len).enumerate() {
+                    line[start + i] = c;
+                }
+            }
+            if let Some(overlay) = overlay {
+                if row == 0 {
+                    for (cell, c) in line.iter_mut().zip(overlay.chars()) {
+                        *cell = c;
+                    }
+                }
+            }
+            out.extend(line);
+            if row + 1 < self.rows {
+                // raw mode doesn't turn \n into \r\n.
+                out.push_str("\r\n");
+            }
+        }
+        out
+    }
+}
+
+// The color of the 256-color palette nearest to rgb: either one of the 6x6x6
+// cube's, or one of the 24 grays.
+fn ansi_color(rgb: [u8; 3]) -> u8 {
+    const LEVELS:[i32; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |v: u8| {
+        (0..LEVELS.len()).min_by_key(|&i| (LEVELS[i] - v as i32).abs()).unwrap_or(0)
+    };
+    let distance = |other: [i32; 3]| {
+        rgb.iter().zip(other.iter()).map(|(&a, &b)| (a as i32 - b).pow(2)).sum::<i32>()
+    };
+
+    let [r, g, b] = [nearest_level(rgb[0]), nearest_level(rgb[1]), nearest_level(rgb[2])];
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let cube_distance = distance([LEVELS[r], LEVELS[g], LEVELS[b]]);
+
+    // the grays go from 8 to 238, 10 apart.
+    let mean = rgb.iter().map(|&v| v as i32).sum::<i32>() / 3;
+    let gray = ((mean - 8 + 5) / 10).clamp(0, 23);
+    let level = 8 + 10 * gray;
+    if distance([level, level, level]) < cube_distance {
+        (232 + gray) as u8
+    } else {
+        cube
+    }
+}
+
+/// Turns the bytes typed into the terminal into inputs.
+pub struct TermKeys {
+    // the direction each paddle is moving in, and for how many more ticks.
+    held: [Option<(UserInput, u32)>; 2],
+    actions: VecDeque<UserInput>,
+    debug_commands: VecDeque<DebugCommand>,
+    two_players: bool,
+    // the start of an escape sequence whose end hasn't been read yet, and
+    // when it was read.
+    pending: Vec<u8>,
+    pending_since: Option<Instant>,
+}
+
+impl TermKeys {
+    pub fn new(two_players: bool) -> TermKeys {
+        TermKeys {
+            held: [None, None],
+            actions: VecDeque::new(),
+            debug_commands: VecDeque::new(),
+            two_players,
+            pending: Vec::new(),
+            pending_since: None,
+        }
+    }
+
+    /// Handle the bytes read from the terminal at once, at the given time.
+    /// An escape sequence cut short is kept until the rest of it comes, or
+    /// until it expires.
+    ///
+    pub fn feed(&mut self, new_bytes: &[u8], now: Instant) {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(new_bytes);
+        let mut i = 0;
+        while i < bytes.len() {
+            let (key, len) = match bytes[i..] {
+                // arrow keys are ESC [ A and ESC [ B, or ESC O A, etc.
+                [ESC, b'[', b'A', ..] | [ESC, b'O', b'A', ..] => (Some((0, UserInput::Up)), 3),
+                [ESC, b'[', b'B', ..] | [ESC, b'O', b'B', ..] => (Some((0, UserInput::Down)), 3),
+                // any other sequence ends with a byte from '@' to '~', e.g.
+                // F5 is ESC [ 1 5 ~.
+                [ESC, b'[', ref rest @ ..] => {
+                    let len = match rest.iter().position(|b| (b'@'..=b'~').contains(b)) {
+                        Some(end) => end + 1,
+                        None => break,
+                    };
+                    if let Some(command) = debug_command_for_sequence(&rest[..len]) {
+                        self.debug_commands.push_back(command);
+                    }
+                    (None, 2 + len)
+                },
+                [ESC, b'O', _, ..] => (None, 3),
+                // the Escape key, or the start of a sequence; see expire.
+                [ESC] | [ESC, b'O'] => break,
+                // an Alt-modified key, or Escape typed right before another
+                // key; either way, not a quit.
+                [ESC, ..] => (None, 1),
+                [c, ..] => {
+                    match c {
+                        b'k' => (Some((0, UserInput::Up)), 1),
+                        b'j' => (Some((0, UserInput::Down)), 1),
+                        b'w' => (Some((1, UserInput::Up)), 1),
+                        b's' => (Some((1, UserInput::Down)), 1),
+                        _ => {
+                            if let Some(action) = action_for_byte(c) {
+                                self.actions.push_back(action);
+                            }
+                            (None, 1)
+                        },
+                    }
+                },
+                [] => break,
+            };
+            if let Some((slot, dir)) = key {
+                if slot == 0 || self.two_players {
+                    self.held[slot] = Some((dir, HOLD_TICKS));
+                }
+            }
+            i += len;
+        }
+        self.pending = bytes[i..].to_vec();
+        if self.pending.is_empty() {
+            self.pending_since = None;
+        } else if self.pending_since.is_none() {
+            self.pending_since = Some(now);
+        }
+    }
+
+    /// Stop waiting for the rest of an escape sequence once ESC_TIMEOUT has
+    /// passed: an ESC with nothing after it is the Escape key, which quits.
+    ///
+    pub fn expire(&mut self, now: Instant) {
+        match self.pending_since {
+            Some(since) if now.duration_since(since) >= ESC_TIMEOUT => {
+                if self.pending == [ESC] {
+                    self.actions.push_back(UserInput::Quit);
+                }
+                self.pending.clear();
+                self.pending_since = None;
+            },
+            _ => {},
+        }
+    }
+
+    /// The inputs for the next tick; actions come before movement.
+    pub fn sample(&mut self) -> Inputs {
+        let mut movement = [UserInput::Nothing; 2];
+        for (held, input) in self.held.iter_mut().zip(movement.iter_mut()) {
+            if let Some((dir, ref mut ticks_left)) = *held {
+                *input = dir;
+                *ticks_left -= 1;
+                if *ticks_left == 0 {
+                    *held = None;
+                }
+            }
+        }
+        Inputs {
+            player: self.actions.pop_front().unwrap_or(movement[0]),
+            second_player: movement[1],
+        }
+    }
+}
+
+// The debug command given by each function key's escape sequence, after
+// the ESC [.
+fn debug_command_for_sequence(sequence: &[u8]) -> Option<DebugCommand> {
+    match sequence {
+        b"15~" => Some(DebugCommand::Pause),
+        b"17~" => Some(DebugCommand::Step),
+        b"18~" => Some(DebugCommand::SlowMotion),
+        b"19~" => Some(DebugCommand::Dump),
+        b"20~" => Some(DebugCommand::Save),
+        b"21~" => Some(DebugCommand::Load),
+        _ => None,
+    }
+}
+
+// The key that triggers each action; ctrl-c quits too, since raw mode
+// doesn't send SIGINT.
+fn action_for_byte(c: u8) -> Option<UserInput> {
+    match c {
+        b'p' => Some(UserInput::Pause),
+        b' ' => Some(UserInput::Serve),
+        b'r' => Some(UserInput::Restart),
+        b'q' | 0x03 => Some(UserInput::Quit),
+        _ => None,
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// The terminal's size, in characters; one row is left out, so that writing
+// the last one doesn't scroll.
+fn terminal_size() -> io::Result<(usize, usize)> {
+    let size = stty(&["size"])?;
+    let mut words = size.split_whitespace().map(|w| w.parse::<usize>().ok());
+    match (words.next(), words.next()) {
+        (Some(Some(rows)), Some(Some(cols))) if rows > 1 && cols > 0 => Ok((cols, rows - 1)),
+        _ => Err(io::Error::other("unexpected output from 'stty size'")),
+    }
+}
+
+/// Puts the terminal into raw mode, and back into whatever mode it was in
+/// when dropped, even if the game panics.
+///
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        // hide the cursor and clear the screen.
+        print!("{}[?25l{}[2J", ESC as char, ESC as char);
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // reset the colors, show the cursor and clear the screen.
+        print!("{}[0m{}[?25h{}[2J{}[H", ESC as char, ESC as char, ESC as char, ESC as char);
+        let _ = io::stdout().flush();
+        if let Err(e) = stty(&[&self.saved]) {
+            eprintln!("failed to restore the terminal: {}", e);
+        }
+    }
+}
+
+// stdin has no non-blocking reads, so it's read by a thread of its own.
+fn spawn_stdin_reader() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 64];
+        loop {
+            match io::stdin().read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if sender.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                },
+            }
+        }
+    });
+    receiver
+}
+
+/// Draws frames in the terminal, which stays in raw mode for as long as
+/// this exists.
+///
+pub struct TerminalRenderer {
+    grid: Grid,
+    colors: Colors,
+    last_resize_check: Instant,
+    // declared last, so that it's dropped after everything's drawn.
+    _raw_mode: RawMode,
+}
+
+/// The keys typed into the terminal.
+pub struct TerminalInput {
+    keys_read: Receiver<Vec<u8>>,
+    keys: TermKeys,
+}
+
+pub fn init(two_players: bool, colors: Colors) -> io::Result<(TerminalRenderer, TerminalInput)> {
+    let (cols, rows) = terminal_size()?;
+    let renderer = TerminalRenderer {
+        grid: Grid::new(cols, rows),
+        colors,
+        last_resize_check: Instant::now(),
+        _raw_mode: RawMode::enable()?,
+    };
+    let input = TerminalInput {
+        keys_read: spawn_stdin_reader(),
+        keys: TermKeys::new(two_players),
+    };
+    Ok((renderer, input))
+}
+
+impl Renderer for TerminalRenderer {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        let now = Instant::now();
+        if now - self.last_resize_check >= RESIZE_CHECK {
+            self.last_resize_check = now;
+            if let Ok((cols, rows)) = terminal_size() {
+                if (cols, rows) != (self.grid.cols, self.grid.rows) {
+                    self.grid = Grid::new(cols, rows);
+                    print!("{}[2J", ESC as char);
+                }
+            }
+        }
+
+        self.grid.clear();
+        for rect in frame.rects {
+            self.grid.fill_rect(*rect);
+        }
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        out.write_all(self.grid.render(&self.colors, frame.text, frame.overlay).as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self) -> bool {
+        while let Ok(bytes) = self.keys_read.try_recv() {
+            self.keys.feed(&bytes, Instant::now());
+        }
+        self.keys.expire(Instant::now());
+        true
+    }
+
+    fn next_inputs(&mut self) -> Inputs {
+        self.keys.sample()
+    }
+
+    fn debug_command(&mut self) -> Option<DebugCommand> {
+        self.keys.debug_commands.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rects_are_scaled() {
+        // each character is 100x100 window pixels, so each grid pixel is
+        // 100 wide and 50 tall.
+        let mut grid = Grid::new(8, 6);
+        grid.fill_rect(Rect::new(150, 50, 1, 100));
+        let screen = grid.render(&Colors::default(), None, None);
+        let lines: Vec<&str> = screen.split("\r\n").collect();
+        assert!(lines[0].ends_with(" ▄      "));
+        assert_eq!(lines[1], " ▀      ");
+        assert_eq!(lines[2], "        ");
+    }
+
+    #[test]
+    fn text_is_centered() {
+        let grid = Grid::new(8, 3);
+        let screen = grid.render(&Colors::default(), Some("HI"), Some("FPS"));
+        let lines: Vec<&str> = screen.split("\r\n").collect();
+        assert!(lines[0].ends_with("FPS     "));
+        assert_eq!(lines[1], "   HI   ");
+
+        // each line of the text gets a row of its own.
+        let grid = Grid::new(8, 5);
+        let screen = grid.render(&Colors::default(), Some("GAME\nR: AGAIN\nQ"), None);
+        let lines: Vec<&str> = screen.split("\r\n").collect();
+        assert!(lines[0].ends_with("        "));
+        assert_eq!(&lines[1..], ["  GAME  ", "R: AGAIN", "   Q    ", "        "]);
+    }
+
+    #[test]
+    fn configured_colors() {
+        let colors = Colors {
+            background: [0, 0, 0x80],
+            foreground: [0x33, 0xff, 0x00],
+        };
+        let screen = Grid::new(2, 1).render(&colors, None, None);
+        assert_eq!(screen, "\x1b[38;5;82m\x1b[48;5;18m\x1b[H  ");
+        assert_eq!(ansi_color([0xff, 0xff, 0xff]), 231);
+        assert_eq!(ansi_color([0, 0, 0]), 16);
+        assert_eq!(ansi_color([0x80, 0x80, 0x80]), 244);
+    }
+
+    #[test]
+    fn arrow_keys_and_actions() {
+        let now = Instant::now();
+        let mut keys = TermKeys::new(false);
+        keys.feed(b"\x1b[Ap", now);
+        assert_eq!(keys.sample().player, UserInput::Pause);
+        assert_eq!(keys.sample().player, UserInput::Up);
+    }
+
+    #[test]
+    fn escape_waits_for_the_rest_of_the_sequence() {
+        let now = Instant::now();
+        let mut keys = TermKeys::new(false);
+        // an arrow key, split across reads.
+        keys.feed(b"\x1b", now);
+        keys.expire(now + ESC_TIMEOUT / 2);
+        keys.feed(b"[", now + ESC_TIMEOUT / 2);
+        keys.feed(b"B", now + ESC_TIMEOUT / 2);
+        assert_eq!(keys.sample().player, UserInput::Down);
+
+        // Escape on its own quits, once nothing else came.
+        let mut keys = TermKeys::new(false);
+        keys.feed(b"\x1b", now);
+        keys.expire(now + ESC_TIMEOUT / 2);
+        assert_eq!(keys.sample().player, UserInput::Nothing);
+        keys.expire(now + ESC_TIMEOUT);
+        assert_eq!(keys.sample().player, UserInput::Quit);
+
+        // but not when followed by another key, nor an unfinished sequence.
+        keys.feed(b"\x1bx\x1b[1", now);
+        keys.expire(now + ESC_TIMEOUT);
+        assert_eq!(keys.sample().player, UserInput::Nothing);
+        assert!(keys.pending.is_empty());
+    }
+
+    #[test]
+    fn function_keys() {
+        let mut keys = TermKeys::new(false);
+        keys.feed(b"\x1b[17~\x1b[1;5Pk\x1b[19~", Instant::now());
+        assert_eq!(keys.debug_commands, [DebugCommand::Step, DebugCommand::Dump]);
+        assert_eq!(keys.sample().player, UserInput::Up);
+    }
+
+    #[test]
+    fn held_keys_time_out() {
+        let mut keys = TermKeys::new(true);
+        keys.feed(b"js", Instant::now());
+        for _ in 0..HOLD_TICKS {
+            assert_eq!(keys.sample(), Inputs {
+                player: UserInput::Down,
+                second_player: UserInput::Down,
+            });
+        }
+        assert_eq!(keys.sample().player, UserInput::Nothing);
+    }
+
+    #[test]
+    fn second_paddle_needs_two_players() {
+        let mut keys = TermKeys::new(false);
+        keys.feed(b"w", Instant::now());
+        assert_eq!(keys.sample().second_player, UserInput::Nothing);
+    }
+}
@@ -0,0 +1,134 @@
+// timing.rs: fixed timestep bookkeeping, so that the game runs at the same
+// speed regardless of the monitor's refresh rate or how long rendering takes.
+//
+
+use std::time::Duration;
+
+pub const DEFAULT_TICK_RATE:u32 = 60;
+
+// When catching up, never run more than this many ticks between two frames;
+// otherwise a backend slower than the tick rate would never render again.
+//
+const MAX_TICKS_PER_FRAME:u32 = 5;
+
+/// What to do when the backend can't run ticks as fast as the tick rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lag {
+    /// Run at most one tick per frame and forget about the time lost, so a
+    /// slow backend plays in slow motion. Best for the lambda backend, where
+    /// ticks are what's slow.
+    SlowDown,
+    /// Run several ticks per frame (up to a limit) to keep up with the real
+    /// time, skipping the frames in between.
+    CatchUp,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Timing {
+    /// Simulation ticks per second.
+    pub tick_rate: u32,
+    /// Whether presenting a frame waits for vsync. If not, frames are
+    /// limited to the tick rate instead.
+    pub vsync: bool,
+    pub lag: Lag,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            tick_rate: DEFAULT_TICK_RATE,
+            vsync: true,
+            lag: Lag::SlowDown,
+        }
+    }
+}
+
+/// Accumulates the real time elapsed and tells how many ticks are due.
+pub struct TickClock {
+    tick: Duration,
+    accumulated: Duration,
+    lag: Lag,
+}
+
+impl TickClock {
+    pub fn new(timing: &Timing) -> TickClock {
+        TickClock {
+            tick: Duration::from_secs(1) / timing.tick_rate.max(1),
+            accumulated: Duration::from_secs(0),
+            lag: timing.lag,
+        }
+    }
+
+    /// Account for elapsed real time, returning how many ticks must be run
+    /// before the next frame.
+    ///
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulated += elapsed;
+        let max_ticks = match self.lag {
+            Lag::SlowDown => 1,
+            Lag::CatchUp => MAX_TICKS_PER_FRAME,
+        };
+
+        let mut ticks = 0;
+        while self.accumulated >= self.tick && ticks < max_ticks {
+            self.accumulated -= self.tick;
+            ticks += 1;
+        }
+        // whatever couldn't be run in time is dropped.
+        if self.accumulated >= self.tick {
+            self.accumulated = Duration::from_secs(0);
+        }
+        ticks
+    }
+
+    /// How long until the next tick is due.
+    pub fn until_next_tick(&self) -> Duration {
+        self.tick - self.accumulated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(lag: Lag) -> TickClock {
+        TickClock::new(&Timing {
+            tick_rate: 100,
+            vsync: false,
+            lag,
+        })
+    }
+
+    #[test]
+    fn ticks_accumulate() {
+        let mut clock = clock(Lag::CatchUp);
+        assert_eq!(clock.advance(Duration::from_millis(4)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(4)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(4)), 1);
+        assert_eq!(clock.until_next_tick(), Duration::from_millis(8));
+    }
+
+    #[test]
+    fn high_refresh_rate_runs_at_tick_rate() {
+        // a 200 Hz monitor with a 100 Hz tick rate: one tick every other frame.
+        let mut clock = clock(Lag::SlowDown);
+        let ticks: u32 = (0..200).map(|_| clock.advance(Duration::from_millis(5))).sum();
+        assert_eq!(ticks, 100);
+    }
+
+    #[test]
+    fn catch_up_is_limited() {
+        let mut clock = clock(Lag::CatchUp);
+        assert_eq!(clock.advance(Duration::from_millis(35)), 3);
+        assert_eq!(clock.advance(Duration::from_millis(1000)), MAX_TICKS_PER_FRAME);
+        assert_eq!(clock.advance(Duration::from_millis(0)), 0);
+    }
+
+    #[test]
+    fn slow_down_drops_lost_time() {
+        let mut clock = clock(Lag::SlowDown);
+        assert_eq!(clock.advance(Duration::from_millis(35)), 1);
+        assert_eq!(clock.advance(Duration::from_millis(0)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(10)), 1);
+    }
+}
@@ -0,0 +1,203 @@
+// window.rs: the SDL window, as a renderer and an input source.
+//
+// The window can be resized and made fullscreen. The game always draws in
+// the same WINDOW_WIDTH by WINDOW_HEIGHT logical space, which SDL scales to
+// fit the window, keeping its aspect ratio, with black bars around it.
+//
+
+use std::collections::VecDeque;
+
+use sdl2::{
+    EventPump,
+    video::{FullscreenType, Window},
+    pixels::Color,
+    event::Event,
+    rect::Rect,
+    render::Canvas,
+    sys::{self, SDL_bool},
+};
+
+use crate::{
+    config::Config,
+    controller::Controllers,
+    debug::DebugCommand,
+    font,
+    game::{Frame, InputSource, Inputs, Renderer, OVERLAY_MARGIN, OVERLAY_SCALE, TEXT_SCALE},
+    input::{self, Keyboard, FULLSCREEN_KEY},
+    pong::{WINDOW_WIDTH, WINDOW_HEIGHT},
+    sound::Speaker,
+};
+
+/// How the window shows the game.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Display {
+    /// Start in fullscreen.
+    pub fullscreen: bool,
+    /// Only scale the logical space by whole numbers, so that every logical
+    /// pixel is the same size.
+    pub integer_scale: bool,
+}
+
+pub struct WindowRenderer {
+    canvas: Canvas<Window>,
+    background: Color,
+    foreground: Color,
+    speaker: Option<Speaker>,
+}
+
+/// The keyboard and game controllers.
+pub struct WindowInput {
+    event_pump: EventPump,
+    keyboard: Keyboard,
+    controllers: Controllers,
+    two_players: bool,
+    debug_commands: VecDeque<DebugCommand>,
+    // the same window as the renderer's canvas, to toggle fullscreen.
+    window: Window,
+}
+
+pub fn game_init(window_name: &str,
+                 vsync: bool,
+                 two_players: bool,
+                 display: Display,
+                 sound: bool,
+                 config: &Config) -> Result<(WindowRenderer, WindowInput), String>
+{
+    let sdl_context = sdl2::init()
+        .map_err(|e| format!("failed to initialize SDL: {}", e))?;
+
+    let video_sys = sdl_context.video()
+        .map_err(|e| format!("failed to initialize video subsystem: {}", e))?;
+
+    let (width, height) = config.window_size;
+    let mut window_builder = video_sys.window(window_name, width, height);
+    window_builder.position_centered().resizable();
+    if display.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let window = window_builder.build()
+        .map_err(|e| format!("failed to create window: {}", e))?;
+
+    let mut canvas_builder = window.into_canvas();
+    if vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build()
+        .map_err(|e| format!("failed to create canvas / renderer: {}", e))?;
+    canvas.set_logical_size(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
+        .map_err(|e| format!("failed to set the logical size: {}", e))?;
+    if display.integer_scale {
+        // sdl2 has no wrapper for this one.
+        let result = unsafe { sys::SDL_RenderSetIntegerScale(canvas.raw(), SDL_bool::SDL_TRUE) };
+        if result != 0 {
+            return Err(format!("failed to set integer scaling: {}", sdl2::get_error()));
+        }
+    }
+    // a second handle to the canvas's window, for the input to toggle
+    // fullscreen with; it shares the window's context, so the window stays
+    // until both are dropped.
+    let window = unsafe { Window::from_ref(canvas.window().context()) };
+
+    let event_pump = sdl_context.event_pump()
+        .map_err(|e| format!("failed to get event pump: {}", e))?;
+
+    let controllers = Controllers::new(&sdl_context);
+
+    // the game can still be played without sound.
+    let speaker = if sound {
+        Speaker::open(&sdl_context)
+            .map_err(|e| eprintln!("failed to open audio, playing without sound: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    let color = |[r, g, b]: [u8; 3]| Color::RGB(r, g, b);
+    let renderer = WindowRenderer {
+        canvas,
+        background: color(config.colors.background),
+        foreground: color(config.colors.foreground),
+        speaker,
+    };
+    let input = WindowInput {
+        event_pump,
+        keyboard: Keyboard::new(two_players, &config.keys),
+        controllers,
+        two_players,
+        debug_commands: VecDeque::new(),
+        window,
+    };
+    Ok((renderer, input))
+}
+
+impl Renderer for WindowRenderer {
+    fn draw(&mut self, frame: &Frame) -> Result<(), String> {
+        self.speaker.draw(frame)?;
+
+        // clearing covers the bars around the logical space too, which are
+        // always black.
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.set_draw_color(self.background);
+        self.canvas.fill_rect(Rect::new(0, 0, WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32))?;
+
+        self.canvas.set_draw_color(self.foreground);
+        self.canvas.fill_rects(frame.rects)?;
+        if let Some(text) = frame.text {
+            let text_rects = font::centered_text_rects(text,
+                                                       WINDOW_WIDTH / 2,
+                                                       WINDOW_HEIGHT / 2,
+                                                       TEXT_SCALE);
+            self.canvas.fill_rects(&text_rects)?;
+        }
+        if let Some(overlay) = frame.overlay {
+            let overlay_rects = font::line_rects(overlay,
+                                                 OVERLAY_MARGIN,
+                                                 OVERLAY_MARGIN,
+                                                 OVERLAY_SCALE);
+            self.canvas.fill_rects(&overlay_rects)?;
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+}
+
+impl InputSource for WindowInput {
+    fn poll(&mut self) -> bool {
+        for event in self.event_pump.poll_iter() {
+            self.controllers.handle_event(&event);
+            match event {
+                Event::Quit {..} => return false,
+
+                Event::KeyDown { keycode: Some(FULLSCREEN_KEY), repeat: false, .. } => {
+                    let fullscreen = match self.window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    if let Err(e) = self.window.set_fullscreen(fullscreen) {
+                        eprintln!("failed to toggle fullscreen: {}", e);
+                    }
+                },
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    match input::debug_command_for_key(keycode) {
+                        Some(command) => self.debug_commands.push_back(command),
+                        None => self.keyboard.key_down(keycode),
+                    }
+                },
+                _ => {},
+            }
+        }
+        true
+    }
+
+    fn next_inputs(&mut self) -> Inputs {
+        let mut inputs = self.keyboard.sample(&self.event_pump);
+        self.controllers.sample(&mut inputs, self.two_players);
+        inputs
+    }
+
+    fn debug_command(&mut self) -> Option<DebugCommand> {
+        self.debug_commands.pop_front()
+    }
+}